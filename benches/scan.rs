@@ -0,0 +1,50 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use remove_water::scan_and_filter;
+use std::hint::black_box;
+
+// About 1 MiB of Rust-looking source, the common case of an already clean tree
+fn ascii_input() -> Vec<u8> {
+    let line = "    let total = items.iter().map(|item| item.len()).sum::<usize>(); // count\n";
+    line.repeat((1 << 20) / line.len()).into_bytes()
+}
+
+// The same amount of text where every line needs cleaning
+fn non_ascii_input() -> Vec<u8> {
+    let line = "    // caf\u{e9} na\u{ef}ve \u{201c}quoted\u{201d} \u{2014} \u{65e5}\u{672c}\u{8a9e} \u{1f642}\n";
+    line.repeat((1 << 20) / line.len()).into_bytes()
+}
+
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_and_filter");
+    for (name, input) in [("ascii", ascii_input()), ("non_ascii", non_ascii_input())] {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(name, |b| b.iter(|| scan_and_filter(black_box(&input))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remove_water::scan_and_filter;
+
+// With the default options the cleaned output is plain ASCII, never longer
+// than the input, and a second run over it finds nothing left to clean
+fuzz_target!(|data: &[u8]| {
+    let scan = scan_and_filter(data);
+    assert!(scan.filtered.is_ascii());
+    assert!(scan.filtered.len() <= data.len());
+
+    let again = scan_and_filter(&scan.filtered);
+    assert!(!again.report.is_dirty());
+    assert_eq!(again.filtered, scan.filtered);
+});
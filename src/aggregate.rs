@@ -0,0 +1,64 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::NonAsciiScan;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Totals over many scans, for callers cleaning more than one buffer. Displays
+// as the one-line batch summary the binary prints
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregateReport {
+    pub files: usize,
+    // Scans whose report was dirty, i.e. whose output differs from the input
+    pub changed: usize,
+    pub skipped_lines: usize,
+    pub non_ascii_bytes: usize,
+    // Occurrences of each non-ASCII character across every scan
+    pub char_counts: BTreeMap<char, usize>,
+}
+
+impl AggregateReport {
+    pub fn add(&mut self, scan: &NonAsciiScan) {
+        let report = &scan.report;
+        self.files += 1;
+        if report.is_dirty() {
+            self.changed += 1;
+        }
+        self.skipped_lines += report.skipped_lines.len();
+        self.non_ascii_bytes += report.non_ascii_bytes.len();
+        for (&ch, &n) in &report.char_counts {
+            *self.char_counts.entry(ch).or_default() += n;
+        }
+    }
+}
+
+impl fmt::Display for AggregateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Processed {} files, {} changed: {} watermark lines skipped, {} non-ASCII bytes removed",
+            self.files, self.changed, self.skipped_lines, self.non_ascii_bytes
+        )
+    }
+}
@@ -0,0 +1,145 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::fs::{
+    File, FileTimes, OpenOptions, canonicalize, metadata, remove_file, rename, symlink_metadata,
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// A file written next to `target` and only renamed over it by `commit`, so an
+// interrupted run (Ctrl-C, power loss) never leaves a half-written target. Dropping it
+// without committing removes the temporary file.
+pub struct AtomicFile {
+    temp: PathBuf,
+    target: PathBuf,
+    file: File,
+    keep_times: bool,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(target: &Path) -> io::Result<AtomicFile> {
+        // Renaming over a symlink would replace the link, so write to what it points at
+        let target = match symlink_metadata(target) {
+            Ok(metadata) if metadata.file_type().is_symlink() => canonicalize(target)?,
+            _ => target.to_path_buf(),
+        };
+        let dir = target.parent().unwrap_or(Path::new(""));
+        let name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        // The same directory keeps the rename on one filesystem
+        let mut counter = 0;
+        loop {
+            let temp = dir.join(format!(".{name}.{}.{counter}.tmp", std::process::id()));
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => {
+                    return Ok(AtomicFile {
+                        temp,
+                        target,
+                        file,
+                        keep_times: false,
+                        committed: false,
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Also carry the target's access and modification times over on commit
+    pub fn keep_times(mut self, keep: bool) -> AtomicFile {
+        self.keep_times = keep;
+        self
+    }
+
+    // Moves the written data over the target, keeping the target's owner and
+    // permissions. The data is synced to disk first so a crash leaves the old or
+    // the new file
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if let Ok(metadata) = metadata(&self.target) {
+            // Only root may give a file away, anyone else keeps their own files
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::{MetadataExt, fchown};
+                match fchown(&self.file, Some(metadata.uid()), Some(metadata.gid())) {
+                    Err(err) if err.kind() != io::ErrorKind::PermissionDenied => return Err(err),
+                    _ => {}
+                }
+            }
+            // Set after the owner, since chown clears the setuid and setgid bits
+            self.file.set_permissions(metadata.permissions())?;
+            if self.keep_times {
+                let times = FileTimes::new()
+                    .set_accessed(metadata.accessed()?)
+                    .set_modified(metadata.modified()?);
+                self.file.set_times(times)?;
+            }
+        }
+        self.file.sync_all()?;
+        rename(&self.temp, &self.target)?;
+        self.committed = true;
+
+        // Make the rename itself durable, where directories can be synced
+        #[cfg(unix)]
+        if let Some(dir) = self.target.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+// Replaces `target` with `data` without ever leaving it half-written
+pub fn write_atomic(target: &Path, data: &[u8], keep_times: bool) -> io::Result<()> {
+    let mut file = AtomicFile::create(target)?.keep_times(keep_times);
+    file.write_all(data)?;
+    file.commit()
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = remove_file(&self.temp);
+        }
+    }
+}
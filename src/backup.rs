@@ -0,0 +1,105 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::atomic::write_atomic;
+use std::fs::{OpenOptions, read, remove_file};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Copies `data` to `<path>.bak`, or `<path>.bak.N` when earlier backups exist,
+// never clobbering an existing file
+pub fn write_backup(path: &str, data: &[u8]) -> io::Result<PathBuf> {
+    let mut counter = 0;
+    loop {
+        let candidate = match counter {
+            0 => PathBuf::from(format!("{path}.bak")),
+            n => PathBuf::from(format!("{path}.bak.{n}")),
+        };
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(mut file) => {
+                file.write_all(data)?;
+                return Ok(candidate);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// Whether `path` is a backup `write_backup` made, a `.bak` or `.bak.N` file
+// next to the file it was copied from
+pub fn is_backup(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let source = match name.rsplit_once(".bak") {
+        Some((source, "")) => source,
+        Some((source, counter))
+            if counter
+                .strip_prefix('.')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())) =>
+        {
+            source
+        }
+        _ => return false,
+    };
+    !source.is_empty() && path.with_file_name(source).is_file()
+}
+
+// The newest backup of `path`, the one with the highest counter
+fn latest_backup(path: &str) -> Option<PathBuf> {
+    let first = PathBuf::from(format!("{path}.bak"));
+    if !first.is_file() {
+        return None;
+    }
+
+    let mut latest = first;
+    for n in 1.. {
+        let candidate = PathBuf::from(format!("{path}.bak.{n}"));
+        if !candidate.is_file() {
+            break;
+        }
+        latest = candidate;
+    }
+    Some(latest)
+}
+
+// Puts the newest backup of `path` back in place and removes it, returning
+// the backup that was used
+pub fn restore_backup(path: &str) -> io::Result<PathBuf> {
+    let backup = latest_backup(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no backup found (looked for {path}.bak)"),
+        )
+    })?;
+
+    write_atomic(Path::new(path), &read(&backup)?, false)?;
+    remove_file(&backup)?;
+    Ok(backup)
+}
@@ -0,0 +1,170 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::atomic::write_atomic;
+use crate::cli::Args;
+use crate::hash::HashAlgo;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{Metadata, read};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub const DEFAULT_PATH: &str = ".nonascii_filter.cache";
+
+// What a clean file looked like when it was last scanned
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    digest: String,
+}
+
+impl Entry {
+    pub fn new(metadata: &Metadata, digest: String) -> Entry {
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Entry {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            digest,
+        }
+    }
+
+    fn same_file(&self, other: &Entry) -> bool {
+        (self.size, self.mtime_secs, self.mtime_nanos)
+            == (other.size, other.mtime_secs, other.mtime_nanos)
+    }
+}
+
+// The sidecar file of --cache: clean files by path, valid only for the options
+// they were scanned with
+#[derive(Default, Deserialize, Serialize)]
+pub struct Cache {
+    options: String,
+    files: BTreeMap<String, Entry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+// What the cache knows about a file before it is read
+pub enum Lookup<'a> {
+    // Same size and modification time as when it was clean
+    Unchanged,
+    // Touched since, clean before if its content still has this digest
+    Modified(&'a str),
+    Unknown,
+}
+
+impl Cache {
+    // A missing or unreadable cache, or one written for other options, starts empty
+    pub fn load(path: &Path, args: &Args) -> Cache {
+        let options = fingerprint(args);
+        let cache = read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Cache>(&data).ok())
+            .filter(|cache| cache.options == options);
+        Cache {
+            path: path.to_path_buf(),
+            ..cache.unwrap_or(Cache {
+                options,
+                ..Cache::default()
+            })
+        }
+    }
+
+    pub fn lookup(&self, path: &str, metadata: &Metadata) -> Lookup<'_> {
+        match self.files.get(path) {
+            Some(entry) if entry.same_file(&Entry::new(metadata, String::new())) => {
+                Lookup::Unchanged
+            }
+            Some(entry) => Lookup::Modified(&entry.digest),
+            None => Lookup::Unknown,
+        }
+    }
+
+    pub fn entry(&self, path: &str) -> Option<Entry> {
+        self.files.get(path).cloned()
+    }
+
+    // Remembers `path` as clean, or forgets it with `None`
+    pub fn update(&mut self, path: String, entry: Option<Entry>) {
+        match entry {
+            Some(entry) => self.files.insert(path, entry),
+            None => self.files.remove(&path),
+        };
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_vec(self)?, false)
+    }
+}
+
+// Everything that decides whether a file counts as clean, so that changing an
+// option or upgrading the tool throws the cache away. Besides the cleaning
+// options these are the passes and checks that can make a file dirty
+fn fingerprint(args: &Args) -> String {
+    let opts = &args.options;
+    let sorted = |chars: &std::collections::HashSet<char>| -> String {
+        chars.iter().collect::<BTreeSet<_>>().into_iter().collect()
+    };
+    let patterns: Vec<_> = opts
+        .patterns
+        .iter()
+        .map(|pattern| format!("{}={}", pattern.name, pattern.regex))
+        .collect();
+    let replace_map: BTreeMap<_, _> = opts.replace_map.iter().collect();
+    let description = format!(
+        "{} {} {:?} {:?} {} {:?} {:?} {} {} {} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {} {} {:?} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        args.hash.name(),
+        opts.replacement,
+        replace_map,
+        opts.transliterate,
+        opts.translit_backend,
+        patterns,
+        opts.whole_line_watermarks,
+        opts.strip_watermarks,
+        opts.detect_encoding,
+        opts.normalize,
+        sorted(&opts.allow),
+        sorted(&opts.deny),
+        opts.allow_ranges,
+        opts.bom,
+        opts.line_ending,
+        opts.controls,
+        opts.drop_emptied,
+        opts.normalize_spaces,
+        opts.skip_long_lines,
+        opts.keep_lines,
+        args.post,
+        args.empty_action,
+    );
+    HashAlgo::Sha256.digest(description.as_bytes())
+}
@@ -0,0 +1,160 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{NonAsciiScan, Options, Position, Report, default_action, prepare, scan_line};
+use log::debug;
+use rayon::prelude::*;
+
+// `scan_with_options` for one large input spread over up to `chunks` rayon
+// tasks. The text is split at line boundaries and every piece is scanned with
+// its real line number and byte offset, so the reassembled output and report
+// are the same as a serial scan's
+pub fn scan_chunked(data: &[u8], opts: &Options, chunks: usize) -> NonAsciiScan {
+    let mut report = Report {
+        input_bytes: data.len(),
+        ..Report::default()
+    };
+    let text = prepare(data, opts, &mut report);
+    let pieces = split_lines(&text, chunks.max(1));
+    debug!("scanning {} bytes in {} chunks", text.len(), pieces.len());
+
+    // Line number of the first line and byte offset of each piece
+    let mut starts = Vec::with_capacity(pieces.len());
+    let (mut line_no, mut offset) = (1, 0);
+    for piece in &pieces {
+        starts.push((line_no, offset));
+        line_no += piece.bytes().filter(|&b| b == b'\n').count();
+        offset += piece.len();
+    }
+
+    let scans: Vec<NonAsciiScan> = pieces
+        .par_iter()
+        .zip(starts)
+        .map(|(piece, (first_line, first_offset))| {
+            let mut scan = NonAsciiScan::default();
+            let mut visit = |position: &Position| default_action(position.ch, opts);
+            let mut start = first_offset;
+            for (idx, raw) in piece.split_inclusive('\n').enumerate() {
+                scan_line(
+                    raw,
+                    first_line + idx,
+                    start,
+                    opts,
+                    &mut visit,
+                    &mut scan.report,
+                    &mut scan.filtered,
+                );
+                start += raw.len();
+            }
+            scan
+        })
+        .collect();
+
+    let mut filtered = Vec::with_capacity(scans.iter().map(|scan| scan.filtered.len()).sum());
+    for scan in scans {
+        filtered.extend_from_slice(&scan.filtered);
+        merge(&mut report, scan.report);
+    }
+    NonAsciiScan { filtered, report }
+}
+
+// Cuts `text` into at most `chunks` pieces of about equal size, each ending
+// with a newline except possibly the last
+fn split_lines(text: &str, chunks: usize) -> Vec<&str> {
+    let size = text.len().div_ceil(chunks).max(1);
+    let mut pieces = Vec::with_capacity(chunks);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = match rest.as_bytes().get(size..) {
+            Some(tail) => tail
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(rest.len(), |at| size + at + 1),
+            None => rest.len(),
+        };
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces
+}
+
+// Appends the report of the piece that follows what `into` covers. The input
+// wide fields, size, encoding and normalization, are already set on `into`
+fn merge(into: &mut Report, from: Report) {
+    let Report {
+        non_ascii_positions,
+        invisible_positions,
+        control_positions,
+        denied_positions,
+        non_ascii_bytes,
+        char_counts,
+        transliterated,
+        untransliterated,
+        skipped_lines,
+        long_lines,
+        emptied_lines,
+        clusters,
+        line_fates,
+        mixed_scripts,
+        zero_width_runs,
+        kept_watermarks,
+        exempted_watermarks,
+        bom,
+        converted_line_endings,
+        normalized_spaces,
+        changed_lines,
+        changed_bytes,
+        input_bytes: _,
+        encoding: _,
+        normalized: _,
+    } = from;
+
+    into.non_ascii_positions.extend(non_ascii_positions);
+    into.invisible_positions.extend(invisible_positions);
+    into.control_positions.extend(control_positions);
+    into.denied_positions.extend(denied_positions);
+    into.non_ascii_bytes.extend(non_ascii_bytes);
+    for (ch, n) in char_counts {
+        *into.char_counts.entry(ch).or_default() += n;
+    }
+    into.transliterated += transliterated;
+    for (ch, n) in untransliterated {
+        *into.untransliterated.entry(ch).or_default() += n;
+    }
+    into.skipped_lines.extend(skipped_lines);
+    into.long_lines.extend(long_lines);
+    into.emptied_lines.extend(emptied_lines);
+    into.clusters.extend(clusters);
+    into.line_fates.extend(line_fates);
+    into.mixed_scripts.extend(mixed_scripts);
+    into.zero_width_runs.extend(zero_width_runs);
+    into.kept_watermarks.extend(kept_watermarks);
+    into.exempted_watermarks.extend(exempted_watermarks);
+    // Only the first piece starts at offset 0, where a byte order mark can be
+    into.bom = into.bom.or(bom);
+    into.converted_line_endings += converted_line_endings;
+    into.normalized_spaces += normalized_spaces;
+    into.changed_lines += changed_lines;
+    into.changed_bytes += changed_bytes;
+}
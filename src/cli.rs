@@ -0,0 +1,875 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::cache;
+use crate::config::{self, Config};
+use crate::git;
+use crate::hash::HashAlgo;
+use crate::walk::WalkOptions;
+use remove_water::{
+    Bom, Controls, LineEnding, Normalization, Options, Pattern, PostPass, TranslitBackend,
+    WATERMARK_PATTERNS, builtin_patterns, find_builtin, parse_patterns, parse_ranges,
+    parse_replace_map,
+};
+use std::fs::{read, read_to_string};
+use std::io::{IsTerminal, Read, stdin};
+use std::path::{Path, PathBuf};
+use std::slice::Iter;
+
+// Where the data to clean comes from
+pub enum Input {
+    Stdin,
+    File(String),
+}
+
+// When the report is coloured
+#[derive(Default)]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+// What happens to a file that cleaning leaves with nothing but whitespace
+#[derive(Debug, Default, PartialEq)]
+pub enum EmptyAction {
+    #[default]
+    Skip,
+    Truncate,
+    // Leave a single newline, what an empty text file is for some tools
+    Newline,
+    Error,
+}
+
+// Command line options for the filter binary
+#[derive(Default)]
+pub struct Args {
+    pub inputs: Vec<Input>,
+    pub write: bool,
+    pub stream: bool,
+    pub output: Option<PathBuf>,
+    pub dry_run: bool,
+    pub check: bool,
+    pub backup: bool,
+    pub force: bool,
+    pub preserve_times: bool,
+    pub verify: bool,
+    pub no_decompress: bool,
+    pub empty_action: EmptyAction,
+    // Formatting passes run over the cleaned content, in order
+    pub post: Vec<PostPass>,
+    // What piped input is called in reports, `<stdin>` unless --stdin-name
+    pub stdin_name: Option<String>,
+    pub interactive: bool,
+    pub yes: bool,
+    pub json: bool,
+    pub print0: bool,
+    // Where --extract writes the removed content, `-` for stdout
+    pub extract: Option<PathBuf>,
+    pub extract_watermarks: bool,
+    pub sarif: bool,
+    pub summary_json: bool,
+    pub stats_only: bool,
+    pub quiet: bool,
+    // How many -v were given, for the log level
+    pub verbosity: u8,
+    pub color: ColorMode,
+    pub diff: bool,
+    pub diff_stat: bool,
+    pub report_file: Option<PathBuf>,
+    pub context: Option<usize>,
+    pub entropy_threshold: Option<f32>,
+    pub max_non_ascii_percent: Option<f64>,
+    pub hex: Option<usize>,
+    pub per_char: bool,
+    pub limit: Option<usize>,
+    pub hash: HashAlgo,
+    pub walk: WalkOptions,
+    pub jobs: Option<usize>,
+    pub threads_per_file: Option<usize>,
+    pub fail_fast: bool,
+    pub cache: Option<PathBuf>,
+    pub options: Options,
+}
+
+const OPTIONS: &str = "\
+options:
+  -w, --write, --fix modify the files; without it they are only reported on
+  -o, --output PATH  write the cleaned copy to PATH and leave the source alone;
+                     PATH must be a directory for several inputs
+  --stream           process files line by line instead of loading them, for
+                     inputs too large for memory
+  --dry-run          report what would change without writing the file, the
+                     default unless --write is given
+  --check            never write; print one line per file and exit with 2 if
+                     any file contains non-ASCII characters or watermarks
+  --backup           copy the original to <file>.bak before overwriting it
+  --files-from LIST  also clean the files named in LIST, one per line, or
+                     read the names from stdin with '-'
+  -0, --null         names in the --files-from list end in NUL instead of a
+                     newline, as printed by find -print0 and git ls-files -z
+  --git-changed      also clean the files git reports as staged, modified or
+                     new below the current directory
+  -i, --interactive  show each file's report and ask before cleaning it
+  -y, --yes          answer yes to every --interactive question
+  --preserve-times   keep the access and modification times of cleaned files
+  --verify           read every written file back and fail unless it scans clean
+  --no-decompress    treat .gz files as raw bytes instead of cleaning their content
+  --post PASS[,PASS] after cleaning also run trim-trailing-ws (spaces and tabs
+                     at line ends) and/or ensure-final-newline; repeatable
+  --empty-action ACTION
+                     what to do with a file cleaning leaves blank: skip it
+                     (the default), truncate it, leave one newline or error
+  --force            clean files even when they look binary
+  --json             print the report as a single JSON object
+  --extract PATH     write the removed non-ASCII bytes to PATH ('-' for stdout)
+                     instead of cleaning anything
+  --extract-watermarks
+                     add the removed watermark lines to --extract, one a line
+  --print0           print every removed character as a NUL terminated
+                     record of tab separated path, line, column and character
+  --sarif            print all findings as one SARIF log for code scanning
+  --summary-json     print one JSON object summing up the whole run, the
+                     per-file reports going to stderr
+  --color WHEN       colour the report: auto (default, only on a terminal and
+                     without NO_COLOR), always or never
+  -q, --quiet        print nothing for clean files, only changes and errors
+  -v, --verbose      also print what happened to every line; -v, -vv and -vvv
+                     log info, debug and trace messages to stderr (RUST_LOG
+                     overrides the level)
+  --stats-only       print only the totals, one compact line per file
+  --diff             print a unified diff of the changes after the report
+  --report-file PATH also append every report to PATH, without colours, to
+                     keep a record of the run (combines with --json)
+  --diff-stat        print only how many lines and bytes change, one line
+                     per file
+  --context N        print N lines around each watermark line after the report
+  --entropy-threshold BITS
+                     only show the entropy of removed bytes (bits per byte,
+                     0 to 8) above BITS; with --check, low-entropy non-ASCII
+                     text alone then passes
+  --max-nonascii-pct PCT
+                     make --check fail when more than PCT percent of a file's
+                     bytes are non-ASCII; below it non-ASCII text alone passes
+  --hex[=N]          hex dump up to N bytes (default 16) of every removed run
+                     of at least 8 bytes; with --entropy-threshold only when
+                     the entropy is above it
+  --limit N          list at most N positions of each kind; the counts stay
+                     complete
+  --stdin-name NAME  call piped input NAME in reports instead of <stdin>
+  --per-char         list removed characters one per line instead of grouping
+                     neighbours into runs
+  --hash ALGO        checksum algorithm for the report: sha256 (default), sha1
+                     or blake3
+  --strip-controls[=all]
+                     also remove ASCII control characters such as VT, FF, NUL
+                     and DEL; with 'all' tabs and stray CRs go too
+  --allow CHARS      keep these non-ASCII characters (e.g. --allow '©→')
+  --deny CHARS       always remove these characters, even ASCII or allowed
+                     ones (e.g. --deny '$')
+  --allow-ranges FILE
+                     keep every character in the hex code point ranges listed
+                     in FILE, one per line (e.g. 0590-05FF for Hebrew)
+  --exclude GLOB     leave files matching GLOB alone, reported as exempt; the
+                     glob is tried against the path, the file name and each
+                     parent directory (repeatable, e.g. --exclude '*.md')
+  --ext LIST         only clean files with these comma separated extensions
+                     when a directory is given (e.g. --ext rs,txt)
+  --max-depth N      descend at most N directory levels, 1 meaning only the
+                     files directly inside a given directory
+  --no-ignore        also clean files excluded by .gitignore and friends when
+                     walking a directory
+  --follow-symlinks  follow symbolic links when walking a directory, each
+                     file being cleaned once however it is reached
+  --jobs N           clean up to N files in parallel, 0 using every core;
+                     reports are then printed sorted by path
+  --threads-per-file N
+                     scan each file larger than 1 MiB as up to N chunks of
+                     whole lines in parallel, 0 using every core
+  --cache[=FILE]     skip files found clean by an earlier run with the same
+                     options, remembered in FILE (.nonascii_filter.cache)
+  --fail-fast        stop at the first file that cannot be read or written
+                     instead of reporting every failure at the end
+  --replace[=STR]    replace each non-ASCII character with STR (default '?')
+                     instead of deleting it
+  --replace-map FILE replace characters by a JSON object in FILE mapping
+                     each character or code point to its replacement; used
+                     before --translit and --replace
+  --translit         transliterate accented letters and smart punctuation to
+                     their closest ASCII equivalent
+  --translit-backend NAME
+                     table used by --translit: simple (the default) or
+                     deunicode, which also romanizes Greek, Cyrillic, CJK, ...
+  --bom ACTION       strip (the default) or keep a UTF-8 byte order mark at
+                     the start of a file; either way it is reported as a BOM
+  --normalize-spaces turn non-breaking, ideographic and other Unicode spaces
+                     into plain spaces instead of removing them
+  --skip-long-lines N
+                     leave lines longer than N characters untouched, e.g.
+                     minified code or base64 blobs, and list them apart
+  --drop-emptied     remove lines that only held removed characters instead
+                     of leaving them blank
+  --zero-width-threshold N
+                     report runs of N or more zero-width characters as
+                     invisible watermarks (default 3, 0 turns this off)
+  --tab-width N      count columns in the text report with tabs expanded to
+                     every N columns, as editors show them (by default a tab
+                     is one column)
+  --graphemes        list removed characters by visible glyph, so a letter
+                     and its combining marks are reported once
+  --normalize FORM   normalize the text to nfc or nfkc before filtering, so
+                     combining marks are handled as precomposed characters
+  --detect-encoding  decode non-UTF-8 input (e.g. Latin-1, UTF-16) before
+                     filtering instead of replacing invalid bytes
+  --line-ending MODE write line endings as lf, crlf or keep (default, each
+                     line keeps its own terminator)
+  --patterns FILE    also treat lines matching any regex in FILE (one per
+                     line) as watermarks
+  --no-builtin-patterns
+                     only use the patterns given with --patterns
+  --disable-pattern NAME
+                     turn off one built-in pattern, by name or its number
+                     in the built-in list (e.g. doc-comment to keep ///)
+  --watermark-whole-line
+                     only treat a line as a watermark when a pattern matches
+                     the entire line, ignoring surrounding whitespace
+  --no-strip-watermarks
+                     keep watermark lines and only list them in the report
+  --keep-line TEXT   never treat a line containing TEXT as a watermark; can be
+                     repeated
+  --config FILE      read defaults from FILE instead of ./.nonascii_filter.toml
+
+exit status:
+  0  every file was already clean
+  1  usage error
+  2  a file was changed (or would be, with --dry-run and --check)
+  3  a file could not be read or written
+  4  a binary file was skipped and nothing else changed";
+
+pub fn usage(program: &str) -> String {
+    format!(
+        "** Non-ASCII + Watermark Filter by Ehud (Udi) Shamir 2025 **\n\
+         usage: {program} [options] <source file or directory>...\n       \
+         {program} [options] - < input > output\n       \
+         {program} restore <file>...   put the newest <file>.bak back\n       \
+         {program} validate [options] <path>...  list non-ASCII and watermarks, exit 2 if any\n\n\
+         {OPTIONS}"
+    )
+}
+
+// Value of an option given either as --flag=value or as the next argument
+fn required_value(
+    flag: &str,
+    value: Option<&str>,
+    rest: &mut Iter<String>,
+) -> Result<String, String> {
+    match value {
+        Some(value) => Ok(value.to_string()),
+        None => rest
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("option '{flag}' requires a value")),
+    }
+}
+
+fn load_patterns(path: &str) -> Result<Vec<Pattern>, String> {
+    let text = read_to_string(path).map_err(|err| format!("cannot read '{path}': {err}"))?;
+    parse_patterns(path, &text).map_err(|err| format!("{path}: {err}"))
+}
+
+// The name of a built-in pattern given by name or 1-based number
+fn builtin_name(name: &str) -> Result<&'static str, String> {
+    find_builtin(name).ok_or_else(|| {
+        let names: Vec<_> = WATERMARK_PATTERNS.iter().map(|(name, _)| *name).collect();
+        format!(
+            "no built-in pattern called '{name}', expected one of {} or 1 to {}",
+            names.join(", "),
+            names.len()
+        )
+    })
+}
+
+impl Args {
+    // Whether stdout carries the --extract output, leaving the reports to stderr
+    pub fn extracts_to_stdout(&self) -> bool {
+        self.extract.as_deref() == Some(Path::new("-"))
+    }
+
+    // How `input` is named in reports
+    pub fn input_name<'a>(&'a self, input: &'a Input) -> &'a str {
+        match input {
+            Input::Stdin => self.stdin_name.as_deref().unwrap_or("<stdin>"),
+            Input::File(path) => path,
+        }
+    }
+
+    // Output meant for other programs, which must not get progress or summaries
+    pub fn machine_output(&self) -> bool {
+        self.json || self.sarif || self.summary_json || self.print0 || self.extracts_to_stdout()
+    }
+}
+
+// Returns Ok(None) when there is nothing to do and the usage should be shown
+pub fn parse_args(argv: &[String]) -> Result<Option<Args>, String> {
+    let mut args = Args::default();
+    let mut builtin = true;
+    let mut patterns = Vec::new();
+    let mut config_path = None;
+    let mut disabled = Vec::new();
+    let mut files_from = None;
+    let mut nul_separated = false;
+    let mut translit_backend = false;
+    let mut git_changed = false;
+    let mut empty_action = false;
+
+    let mut rest = argv[1..].iter();
+    while let Some(arg) = rest.next() {
+        // Options taking a value accept it as --flag=value
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+
+        match (flag, value) {
+            ("-h" | "--help", None) => return Ok(None),
+            ("-w" | "--write" | "--fix", None) => args.write = true,
+            ("-o" | "--output", value) => {
+                args.output = Some(required_value(flag, value, &mut rest)?.into())
+            }
+            ("--stream", None) => args.stream = true,
+            ("--dry-run", None) => args.dry_run = true,
+            ("--check", None) => args.check = true,
+            ("--backup", None) => args.backup = true,
+            ("--force", None) => args.force = true,
+            ("--fail-fast", None) => args.fail_fast = true,
+            ("--cache", value) => {
+                args.cache = Some(PathBuf::from(value.unwrap_or(cache::DEFAULT_PATH)));
+            }
+            ("--preserve-times", None) => args.preserve_times = true,
+            ("--verify", None) => args.verify = true,
+            ("--no-decompress", None) => args.no_decompress = true,
+            ("--files-from", value) => files_from = Some(required_value(flag, value, &mut rest)?),
+            ("-0" | "--null", None) => nul_separated = true,
+            ("--git-changed", None) => git_changed = true,
+            ("-i" | "--interactive", None) => args.interactive = true,
+            ("-y" | "--yes", None) => args.yes = true,
+            ("--json", None) => args.json = true,
+            ("--print0", None) => args.print0 = true,
+            ("--extract", value) => {
+                args.extract = Some(PathBuf::from(required_value(flag, value, &mut rest)?));
+            }
+            ("--extract-watermarks", None) => args.extract_watermarks = true,
+            ("--sarif", None) => args.sarif = true,
+            ("--summary-json", None) => args.summary_json = true,
+            ("--diff", None) => args.diff = true,
+            ("--diff-stat", None) => args.diff_stat = true,
+            ("--report-file", value) => {
+                args.report_file = Some(required_value(flag, value, &mut rest)?.into())
+            }
+            ("--stats-only", None) => args.stats_only = true,
+            ("-q" | "--quiet", None) => args.quiet = true,
+            ("-v" | "-vv" | "-vvv" | "--verbose", None) => {
+                args.options.record_lines = true;
+                args.verbosity += match flag {
+                    "-vv" => 2,
+                    "-vvv" => 3,
+                    _ => 1,
+                };
+            }
+            ("--post", value) => {
+                for name in required_value(flag, value, &mut rest)?.split(',') {
+                    args.post.push(match name {
+                        "trim-trailing-ws" => PostPass::TrimTrailingWhitespace,
+                        "ensure-final-newline" => PostPass::EnsureFinalNewline,
+                        other => {
+                            return Err(format!(
+                                "--post expects trim-trailing-ws or ensure-final-newline, got '{other}'"
+                            ));
+                        }
+                    });
+                }
+            }
+            ("--empty-action", value) => {
+                args.empty_action = match required_value(flag, value, &mut rest)?.as_str() {
+                    "skip" => EmptyAction::Skip,
+                    "truncate" => EmptyAction::Truncate,
+                    "newline" => EmptyAction::Newline,
+                    "error" => EmptyAction::Error,
+                    other => {
+                        return Err(format!(
+                            "--empty-action expects skip, truncate, newline or error, got '{other}'"
+                        ));
+                    }
+                };
+                empty_action = true;
+            }
+            ("--color", value) => {
+                args.color = match required_value(flag, value, &mut rest)?.as_str() {
+                    "auto" => ColorMode::Auto,
+                    "always" => ColorMode::Always,
+                    "never" => ColorMode::Never,
+                    other => {
+                        return Err(format!(
+                            "--color expects auto, always or never, got '{other}'"
+                        ));
+                    }
+                };
+            }
+            ("--line-ending", value) => {
+                args.options.line_ending = match required_value(flag, value, &mut rest)?.as_str() {
+                    "keep" => LineEnding::Keep,
+                    "lf" => LineEnding::Lf,
+                    "crlf" => LineEnding::Crlf,
+                    other => {
+                        return Err(format!(
+                            "--line-ending expects lf, crlf or keep, got '{other}'"
+                        ));
+                    }
+                };
+            }
+            ("--strip-controls", None) => args.options.controls = Controls::Strip,
+            ("--strip-controls", Some("all")) => args.options.controls = Controls::StripAll,
+            ("--allow", value) => {
+                let chars = required_value(flag, value, &mut rest)?;
+                args.options.allow.extend(chars.chars());
+            }
+            ("--deny", value) => {
+                let chars = required_value(flag, value, &mut rest)?;
+                args.options.deny.extend(chars.chars());
+            }
+            ("--allow-ranges", value) => {
+                let path = required_value(flag, value, &mut rest)?;
+                let text =
+                    read_to_string(&path).map_err(|err| format!("cannot read '{path}': {err}"))?;
+                let ranges = parse_ranges(&text).map_err(|err| format!("{path}: {err}"))?;
+                args.options.allow_ranges.extend(ranges);
+            }
+            ("--exclude", value) => {
+                let glob = required_value(flag, value, &mut rest)?;
+                let pattern = glob::Pattern::new(&glob)
+                    .map_err(|err| format!("--exclude got an invalid glob '{glob}': {err}"))?;
+                args.walk.exclude.push(pattern);
+            }
+            ("--ext", value) => {
+                let list = required_value(flag, value, &mut rest)?;
+                args.walk.extensions = list
+                    .split(',')
+                    .map(|ext| ext.trim().trim_start_matches('.').to_string())
+                    .filter(|ext| !ext.is_empty())
+                    .collect();
+            }
+            ("--max-depth", value) => {
+                let depth = required_value(flag, value, &mut rest)?;
+                let depth = depth
+                    .parse()
+                    .map_err(|_| format!("--max-depth expects a number, got '{depth}'"))?;
+                args.walk.max_depth = Some(depth);
+            }
+            ("--entropy-threshold", value) => {
+                let bits = required_value(flag, value, &mut rest)?;
+                let bits: f32 = bits
+                    .parse()
+                    .map_err(|_| format!("--entropy-threshold expects a number, got '{bits}'"))?;
+                if !(0.0..=8.0).contains(&bits) {
+                    return Err(format!(
+                        "--entropy-threshold is in bits per byte, between 0 and 8, got {bits}"
+                    ));
+                }
+                args.entropy_threshold = Some(bits);
+            }
+            ("--max-nonascii-pct", value) => {
+                let pct = required_value(flag, value, &mut rest)?;
+                let pct = pct
+                    .parse()
+                    .ok()
+                    .filter(|pct| (0.0..=100.0).contains(pct))
+                    .ok_or_else(|| {
+                        format!("--max-nonascii-pct expects a percentage, got '{pct}'")
+                    })?;
+                args.max_non_ascii_percent = Some(pct);
+            }
+            ("--context", value) => {
+                let lines = required_value(flag, value, &mut rest)?;
+                let lines = lines
+                    .parse()
+                    .map_err(|_| format!("--context expects a number of lines, got '{lines}'"))?;
+                args.context = Some(lines);
+            }
+            ("--per-char", None) => args.per_char = true,
+            ("--stdin-name", value) => {
+                args.stdin_name = Some(required_value(flag, value, &mut rest)?);
+            }
+            ("--hex", value) => {
+                let len = value.unwrap_or("16");
+                let len = len
+                    .parse()
+                    .map_err(|_| format!("--hex expects a number of bytes, got '{len}'"))?;
+                args.hex = Some(len);
+            }
+            ("--hash", value) => {
+                let name = required_value(flag, value, &mut rest)?;
+                args.hash = HashAlgo::parse(&name).ok_or_else(|| {
+                    format!("--hash expects sha256, sha1 or blake3, got '{name}'")
+                })?;
+            }
+            ("--no-ignore", None) => args.walk.respect_ignore = false,
+            ("--follow-symlinks", None) => args.walk.follow_links = true,
+            ("--limit", value) => {
+                let limit = required_value(flag, value, &mut rest)?;
+                let limit = limit
+                    .parse()
+                    .map_err(|_| format!("--limit expects a number, got '{limit}'"))?;
+                args.limit = Some(limit);
+            }
+            ("--jobs", value) => {
+                let jobs = required_value(flag, value, &mut rest)?;
+                let jobs = jobs
+                    .parse()
+                    .map_err(|_| format!("--jobs expects a number, got '{jobs}'"))?;
+                args.jobs = Some(jobs);
+            }
+            ("--threads-per-file", value) => {
+                let threads = required_value(flag, value, &mut rest)?;
+                let threads = threads
+                    .parse()
+                    .map_err(|_| format!("--threads-per-file expects a number, got '{threads}'"))?;
+                args.threads_per_file = Some(threads);
+            }
+            ("--normalize", value) => {
+                args.options.normalize = match required_value(flag, value, &mut rest)?.as_str() {
+                    "nfc" => Some(Normalization::Nfc),
+                    "nfkc" => Some(Normalization::Nfkc),
+                    other => {
+                        return Err(format!("--normalize expects nfc or nfkc, got '{other}'"));
+                    }
+                };
+            }
+            ("--detect-encoding", None) => args.options.detect_encoding = true,
+            ("--zero-width-threshold", value) => {
+                let threshold = required_value(flag, value, &mut rest)?;
+                args.options.zero_width_threshold = threshold.parse().map_err(|_| {
+                    format!("--zero-width-threshold expects a number, got '{threshold}'")
+                })?;
+            }
+            ("--tab-width", value) => {
+                let width = required_value(flag, value, &mut rest)?;
+                let width = width
+                    .parse()
+                    .map_err(|_| format!("--tab-width expects a positive number, got '{width}'"))?;
+                args.options.tab_width = Some(width);
+            }
+            ("--bom", value) => {
+                args.options.bom = match required_value(flag, value, &mut rest)?.as_str() {
+                    "strip" => Bom::Strip,
+                    "keep" => Bom::Keep,
+                    other => return Err(format!("--bom expects strip or keep, got '{other}'")),
+                };
+            }
+            ("--skip-long-lines", value) => {
+                let max = required_value(flag, value, &mut rest)?;
+                let max = max
+                    .parse()
+                    .map_err(|_| format!("--skip-long-lines expects a number, got '{max}'"))?;
+                args.options.skip_long_lines = Some(max);
+            }
+            ("--normalize-spaces", None) => args.options.normalize_spaces = true,
+            ("--translit", None) => args.options.transliterate = true,
+            ("--translit-backend", value) => {
+                args.options.translit_backend =
+                    match required_value(flag, value, &mut rest)?.as_str() {
+                        "simple" => TranslitBackend::Simple,
+                        "deunicode" => TranslitBackend::Deunicode,
+                        other => {
+                            return Err(format!(
+                                "--translit-backend expects simple or deunicode, got '{other}'"
+                            ));
+                        }
+                    };
+                translit_backend = true;
+            }
+            ("--replace-map", value) => {
+                let path = required_value(flag, value, &mut rest)?;
+                let text =
+                    read_to_string(&path).map_err(|err| format!("cannot read '{path}': {err}"))?;
+                args.options.replace_map =
+                    parse_replace_map(&text).map_err(|err| format!("{path}: {err}"))?;
+            }
+            ("--graphemes", None) => args.options.graphemes = true,
+            ("--drop-emptied", None) => args.options.drop_emptied = true,
+            ("--patterns", value) => {
+                let path = required_value(flag, value, &mut rest)?;
+                patterns.extend(load_patterns(&path)?);
+            }
+            ("--no-builtin-patterns", None) => builtin = false,
+            ("--disable-pattern", value) => {
+                disabled.push(builtin_name(&required_value(flag, value, &mut rest)?)?)
+            }
+            ("--no-strip-watermarks", None) => args.options.strip_watermarks = false,
+            ("--keep-line", value) => {
+                let substring = required_value(flag, value, &mut rest)?;
+                args.options.keep_lines.push(substring);
+            }
+            ("--config", value) => config_path = Some(required_value(flag, value, &mut rest)?),
+            ("--watermark-whole-line", None) => args.options.whole_line_watermarks = true,
+            ("--replace", value) => {
+                args.options.replacement = Some(value.unwrap_or("?").to_string())
+            }
+            (flag, _) if flag.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            ("-", _) => args.inputs.push(Input::Stdin),
+            (file, _) => args.inputs.push(Input::File(file.to_string())),
+        }
+    }
+
+    // Flags win over the config file, which wins over the defaults
+    let config = match config_path {
+        Some(path) => config::load(&path)?,
+        None if Path::new(config::DEFAULT_PATH).is_file() => config::load(config::DEFAULT_PATH)?,
+        None => Config::default(),
+    };
+    if args.options.replacement.is_none() {
+        args.options.replacement = config.replace;
+    }
+    if args.options.allow.is_empty()
+        && let Some(chars) = config.allow
+    {
+        args.options.allow.extend(chars.chars());
+    }
+    if patterns.is_empty() {
+        patterns = config.compiled;
+    }
+    builtin &= config.builtin_patterns.unwrap_or(true);
+    args.options.whole_line_watermarks |= config.watermark_whole_line.unwrap_or(false);
+    args.entropy_threshold = args.entropy_threshold.or(config.entropy_threshold);
+    if args.options.keep_lines.is_empty() {
+        args.options.keep_lines = config.keep_lines;
+    }
+
+    for name in &config.disable_patterns {
+        disabled.extend(find_builtin(name));
+    }
+
+    args.options.patterns = if builtin {
+        builtin_patterns().map_err(|err| format!("invalid built-in watermark pattern: {err}"))?
+    } else {
+        Vec::new()
+    };
+    args.options
+        .patterns
+        .retain(|pattern| !disabled.contains(&pattern.name.as_str()));
+    args.options.patterns.extend(patterns);
+
+    if translit_backend && !args.options.transliterate {
+        return Err("--translit-backend only applies with --translit".to_string());
+    }
+    if nul_separated && files_from.is_none() {
+        return Err("-0 only applies to the list read with --files-from".to_string());
+    }
+    if let Some(source) = &files_from {
+        let list = match source.as_str() {
+            "-" => {
+                let mut list = Vec::new();
+                stdin()
+                    .read_to_end(&mut list)
+                    .map_err(|err| format!("cannot read the file list from stdin: {err}"))?;
+                list
+            }
+            path => read(path).map_err(|err| format!("cannot read '{path}': {err}"))?,
+        };
+        let separator = if nul_separated { b'\0' } else { b'\n' };
+        for name in list.split(|&byte| byte == separator) {
+            let name = String::from_utf8_lossy(name);
+            let name = if nul_separated {
+                &name
+            } else {
+                name.trim_end_matches('\r')
+            };
+            if !name.is_empty() {
+                args.inputs.push(Input::File(name.to_string()));
+            }
+        }
+        if source == "-" && args.interactive {
+            return Err(
+                "--interactive reads its answers from stdin, so the list cannot come from there"
+                    .to_string(),
+            );
+        }
+    }
+
+    if git_changed {
+        args.inputs
+            .extend(git::changed_files()?.into_iter().map(Input::File));
+    }
+
+    // Without a path fall back to stdin, unless nothing is being piped in
+    if args.inputs.is_empty() && files_from.is_none() && !git_changed {
+        if stdin().is_terminal() {
+            return Ok(None);
+        }
+        args.inputs.push(Input::Stdin);
+    }
+
+    let reads_stdin = args
+        .inputs
+        .iter()
+        .any(|input| matches!(input, Input::Stdin));
+    if reads_stdin && args.inputs.len() > 1 {
+        return Err("stdin ('-') cannot be combined with other source files".to_string());
+    }
+    if reads_stdin && args.summary_json && args.output.is_none() {
+        return Err("--summary-json needs stdout, which carries the cleaned stdin".to_string());
+    }
+    if reads_stdin && args.backup {
+        return Err("--backup needs a source file, not stdin".to_string());
+    }
+
+    if args.extract.is_some() {
+        if args.write || args.output.is_some() || args.interactive {
+            return Err(
+                "--extract keeps what would be removed and never writes, drop --write, --output and --interactive"
+                    .to_string(),
+            );
+        }
+        if args.extracts_to_stdout()
+            && (args.json || args.sarif || args.summary_json || args.print0)
+        {
+            return Err(
+                "--extract - needs stdout, drop --json, --sarif, --summary-json and --print0"
+                    .to_string(),
+            );
+        }
+        args.dry_run = true;
+    } else if args.extract_watermarks {
+        return Err("--extract-watermarks only applies to --extract".to_string());
+    }
+
+    if args.interactive {
+        if reads_stdin || args.output.is_some() {
+            return Err(
+                "--interactive asks before overwriting source files, drop '-' and --output"
+                    .to_string(),
+            );
+        }
+        if args.jobs.is_some() || args.machine_output() || args.quiet {
+            return Err(
+                "--interactive cannot be combined with --jobs, --quiet or machine readable output"
+                    .to_string(),
+            );
+        }
+        if args.dry_run || args.check {
+            return Err(
+                "--interactive writes what is confirmed, drop --dry-run and --check".to_string(),
+            );
+        }
+        if args.report_file.is_some() {
+            return Err(
+                "--interactive prints its reports as it goes, drop --report-file".to_string(),
+            );
+        }
+        args.write = true;
+    }
+
+    // Files are only modified on request; stdin always gets its cleaned copy on stdout
+    if args.output.is_some() {
+        if args.dry_run || args.check {
+            return Err("--output writes a cleaned copy, drop --dry-run and --check".to_string());
+        }
+        if args.backup {
+            return Err("--output never touches the source, so --backup is not needed".to_string());
+        }
+        args.write = true;
+    }
+    if args.write && args.dry_run {
+        return Err("--dry-run and --write contradict each other".to_string());
+    }
+    if args.write && args.check {
+        return Err("--check never writes, drop --write".to_string());
+    }
+    if args.backup && !args.write && !args.check {
+        return Err("--backup only applies when files are written, add --write".to_string());
+    }
+    if args.verify && !args.write {
+        return Err("--verify reads back written files, add --write".to_string());
+    }
+    if !args.write && !reads_stdin {
+        args.dry_run = true;
+    }
+
+    // Machine readable columns count code points, as the SARIF log declares
+    if args.json || args.sarif || args.print0 {
+        args.options.tab_width = None;
+    }
+
+    if args.quiet && args.options.record_lines {
+        return Err("--quiet and --verbose contradict each other".to_string());
+    }
+
+    // These need the whole input at once
+    if args.stream {
+        let whole = [
+            ("--diff", args.diff),
+            ("--context", args.context.is_some()),
+            ("--backup", args.backup),
+            ("--detect-encoding", args.options.detect_encoding),
+            ("--empty-action", empty_action),
+            ("--post", !args.post.is_empty()),
+            ("--threads-per-file", args.threads_per_file.is_some()),
+        ];
+        if let Some((flag, _)) = whole.iter().find(|(_, set)| *set) {
+            return Err(format!("{flag} cannot be combined with --stream"));
+        }
+    }
+
+    if args.sarif && args.summary_json {
+        return Err("--sarif and --summary-json both print to stdout, pick one".to_string());
+    }
+    if args.sarif && (args.json || args.check || args.stats_only) {
+        return Err(
+            "--sarif is an output format of its own, drop --json, --check and --stats-only"
+                .to_string(),
+        );
+    }
+
+    let formats = [
+        args.json,
+        args.sarif,
+        args.check,
+        args.stats_only,
+        args.diff,
+    ];
+    if args.diff_stat && formats.contains(&true) {
+        return Err(
+            "--diff-stat prints one line per file, drop --json, --sarif, --check, \
+             --stats-only and --diff"
+                .to_string(),
+        );
+    }
+    if args.check && args.stats_only {
+        return Err("--check already prints one line per file, drop --stats-only".to_string());
+    }
+    if args.check && args.backup {
+        return Err("--check never writes, so --backup makes no sense with it".to_string());
+    }
+
+    Ok(Some(args))
+}
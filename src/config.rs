@@ -0,0 +1,79 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use remove_water::{Pattern, find_builtin};
+use serde::Deserialize;
+use std::fs::read_to_string;
+
+// Picked up from the working directory when no --config is given
+pub const DEFAULT_PATH: &str = ".nonascii_filter.toml";
+
+// A repository's cleaning policy; keys are named after the matching flags
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub replace: Option<String>,
+    pub allow: Option<String>,
+    pub patterns: Vec<String>,
+    pub builtin_patterns: Option<bool>,
+    pub disable_patterns: Vec<String>,
+    pub watermark_whole_line: Option<bool>,
+    pub keep_lines: Vec<String>,
+    pub entropy_threshold: Option<f32>,
+    #[serde(skip)]
+    pub compiled: Vec<Pattern>,
+}
+
+// Reads and validates `path`, so a broken policy fails before any file is touched
+pub fn load(path: &str) -> Result<Config, String> {
+    let text = read_to_string(path).map_err(|err| format!("cannot read '{path}': {err}"))?;
+    let mut config: Config = toml::from_str(&text).map_err(|err| format!("{path}: {err}"))?;
+
+    config.compiled = config
+        .patterns
+        .iter()
+        .enumerate()
+        .map(|(n, pattern)| {
+            Pattern::new(&format!("{path}:{}", n + 1), pattern)
+                .map_err(|err| format!("{path}: invalid pattern '{pattern}': {err}"))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if let Some(name) = config
+        .disable_patterns
+        .iter()
+        .find(|name| find_builtin(name).is_none())
+    {
+        return Err(format!("{path}: no built-in pattern called '{name}'"));
+    }
+
+    if let Some(bits) = config.entropy_threshold
+        && !(0.0..=8.0).contains(&bits)
+    {
+        return Err(format!(
+            "{path}: entropy-threshold must be between 0 and 8, got {bits}"
+        ));
+    }
+
+    Ok(config)
+}
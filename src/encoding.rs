@@ -0,0 +1,58 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE};
+use std::borrow::Cow;
+
+// Decodes `data` to text. Valid UTF-8 is used as is; otherwise, when `detect`
+// is set, a UTF-16 BOM or a chardetng guess picks the encoding, whose name is
+// returned. Anything that still does not decode cleanly falls back to lossy
+// UTF-8 with U+FFFD replacement characters.
+pub fn decode(data: &[u8], detect: bool) -> (Cow<'_, str>, Option<&'static str>) {
+    if let Ok(text) = std::str::from_utf8(data) {
+        return (Cow::Borrowed(text), None);
+    }
+    if !detect {
+        return (String::from_utf8_lossy(data), None);
+    }
+
+    let (encoding, bom_len) = match encoding_rs::Encoding::for_bom(data) {
+        Some((encoding, len)) if encoding == UTF_16LE || encoding == UTF_16BE => (encoding, len),
+        _ => {
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+            detector.feed(data, true);
+            (detector.guess(None, Utf8Detection::Allow), 0)
+        }
+    };
+
+    // A UTF-8 guess for invalid UTF-8 means the detector has no better idea
+    if encoding != UTF_8
+        && let Some(text) =
+            encoding.decode_without_bom_handling_and_without_replacement(&data[bom_len..])
+    {
+        return (Cow::Owned(text.into_owned()), Some(encoding.name()));
+    }
+
+    (String::from_utf8_lossy(data), None)
+}
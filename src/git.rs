@@ -0,0 +1,73 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::path::Path;
+use std::process::Command;
+
+// Runs git with `args` and splits its NUL separated output into paths
+fn git(args: &[&str]) -> Result<Vec<String>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|err| format!("cannot run git: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().next().unwrap_or("failed");
+        return Err(format!("git {}: {reason}", args.join(" ")));
+    }
+
+    Ok(output
+        .stdout
+        .split(|&byte| byte == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect())
+}
+
+// Files below the working directory that are staged, modified or new compared
+// to HEAD, for --git-changed. Deleted files are left out, and paths are
+// relative to the working directory so nothing outside it is listed
+pub fn changed_files() -> Result<Vec<String>, String> {
+    if git(&["rev-parse", "--is-inside-work-tree"]).is_err() {
+        return Err("--git-changed only works inside a git working tree".to_string());
+    }
+    // A repository without commits has no HEAD to compare with yet
+    let mut files = match git(&["rev-parse", "--verify", "--quiet", "HEAD"]) {
+        Ok(_) => git(&[
+            "diff",
+            "--name-only",
+            "--relative",
+            "-z",
+            "--diff-filter=ACMR",
+            "HEAD",
+        ])?,
+        Err(_) => git(&["diff", "--name-only", "--relative", "-z", "--cached"])?,
+    };
+    files.extend(git(&["ls-files", "--others", "--exclude-standard", "-z"])?);
+
+    files.sort();
+    files.dedup();
+    // A file can vanish between git listing it and the run reaching it
+    files.retain(|path| Path::new(path).is_file());
+    Ok(files)
+}
@@ -0,0 +1,1635 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+mod aggregate;
+mod chunked;
+mod encoding;
+mod invisible;
+mod options;
+mod post;
+mod ranges;
+mod replace_map;
+mod script;
+mod stream;
+mod translit;
+mod watermark;
+
+pub use aggregate::AggregateReport;
+pub use chunked::scan_chunked;
+pub use encoding::decode;
+pub use invisible::{control_name, invisible_name, is_bidi_control, is_zero_width};
+pub use options::{Bom, Controls, LineEnding, Normalization, Options, OptionsBuilder};
+pub use post::{PostPass, post_process};
+pub use ranges::{RangeError, parse_ranges};
+pub use replace_map::{ReplaceMapError, parse_replace_map};
+pub use script::{Script, mixed_scripts, script_of};
+pub use stream::scan_reader;
+pub use translit::{TranslitBackend, transliterate, transliterate_with};
+pub use watermark::{
+    Pattern, PatternError, WATERMARK_PATTERNS, builtin_patterns, compile_pattern, compile_patterns,
+    find_builtin, parse_patterns,
+};
+
+use log::{debug, trace};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+// A line dropped because it matched a watermark pattern
+pub struct SkippedLine {
+    // 1-based line number and character column of the match
+    pub line: usize,
+    pub column: usize,
+    // The text the pattern matched and the full original line
+    pub mark: String,
+    pub text: String,
+    // Name of the pattern that matched, see `Pattern::name`
+    pub pattern: String,
+}
+
+// Longest watermark banner `Report::watermark_blocks` looks for
+const MAX_BANNER_LINES: usize = 64;
+
+// Adjacent removed watermark lines, and the lines where the same block of
+// text turned up again, like a banner repeated at the top of every section
+pub struct WatermarkBlock<'a> {
+    // The first occurrence
+    pub lines: &'a [SkippedLine],
+    // First line of every occurrence, the first one included
+    pub starts: Vec<usize>,
+}
+
+// Where a removed character was found
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    // 1-based line number and column counted in characters
+    pub line: usize,
+    pub column: usize,
+    // Byte offset of the character in the original input, or in the decoded
+    // text when the input was not valid UTF-8 or got normalized
+    pub offset: usize,
+    pub ch: char,
+}
+
+// A word mixing letters of look-alike scripts, e.g. a Cyrillic `a` in `paypal`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixedScript {
+    pub line: usize,
+    pub column: usize,
+    pub word: String,
+    pub scripts: Vec<Script>,
+}
+
+// Consecutive zero-width characters, the shape of an invisible watermark
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZeroWidthRun {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    pub text: String,
+}
+
+// One visible glyph holding removed characters, e.g. `n` followed by a
+// combining tilde. Columns count grapheme clusters rather than code points
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    pub text: String,
+}
+
+// What happened to a line, recorded when `Options::record_lines` is set
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineFate {
+    Kept,
+    // Dropped as a watermark, or kept when watermarks are not stripped
+    Watermark,
+    WatermarkKept,
+    // Had this many characters removed or replaced
+    Stripped(usize),
+    // Left untouched for being longer than `Options::skip_long_lines`
+    TooLong,
+}
+
+// Consecutive removed characters, located by the first one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Run {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    // Number of characters and their original bytes
+    pub chars: usize,
+    pub bytes: Vec<u8>,
+}
+
+// Everything a scan found and removed, without the cleaned bytes themselves
+#[derive(Default)]
+pub struct Report {
+    pub non_ascii_positions: Vec<Position>,
+    // Zero-width and bidi controls, kept apart from `non_ascii_positions`
+    pub invisible_positions: Vec<Position>,
+    // ASCII control characters removed by `Options::controls`
+    pub control_positions: Vec<Position>,
+    // Characters removed because they are in `Options::deny`
+    pub denied_positions: Vec<Position>,
+    pub non_ascii_bytes: Vec<u8>,
+    // Occurrences of each distinct non-ASCII character, invisible ones included
+    pub char_counts: BTreeMap<char, usize>,
+    // How many of the non-ASCII characters were transliterated rather than removed
+    pub transliterated: usize,
+    // Characters `Options::transliterate` had no spelling for, which fell back
+    // to `replacement` or removal, with their number of occurrences
+    pub untransliterated: BTreeMap<char, usize>,
+    pub skipped_lines: Vec<SkippedLine>,
+    // 1-based numbers of lines left untouched by `Options::skip_long_lines`
+    pub long_lines: Vec<usize>,
+    // 1-based numbers of lines left blank by the filter, dropped entirely
+    // with `Options::drop_emptied`
+    pub emptied_lines: Vec<usize>,
+    // Removed non-ASCII characters grouped by glyph, empty unless `Options::graphemes`
+    pub clusters: Vec<Cluster>,
+    // The fate of every line in order, empty unless `Options::record_lines`
+    pub line_fates: Vec<LineFate>,
+    // Possible homoglyph spoofing, reported apart from ordinary non-ASCII text
+    pub mixed_scripts: Vec<MixedScript>,
+    // Invisible watermarks, see `Options::zero_width_threshold`
+    pub zero_width_runs: Vec<ZeroWidthRun>,
+    // Watermarks found but left in place because stripping them was disabled
+    pub kept_watermarks: Vec<SkippedLine>,
+    // Watermark matches left in place because of `Options::keep_lines`
+    pub exempted_watermarks: Vec<SkippedLine>,
+    // Set when the input started with a UTF-8 byte order mark, to what
+    // `Options::bom` did with it
+    pub bom: Option<Bom>,
+    // Lines whose terminator was rewritten by `Options::line_ending`
+    pub converted_line_endings: usize,
+    // Non-ASCII whitespace turned into spaces by `Options::normalize_spaces`
+    pub normalized_spaces: usize,
+    // Lines of the scanned text the output differs in, and how many of their
+    // bytes were removed or rewritten; a cheap stand-in for a diff
+    pub changed_lines: usize,
+    pub changed_bytes: usize,
+    // Size of the scanned input, the base of `non_ascii_percent`
+    pub input_bytes: usize,
+    // Encoding the input was decoded from when it was not UTF-8
+    pub encoding: Option<&'static str>,
+    // Form applied by `Options::normalize` and the text size before and after it
+    pub normalized: Option<(Normalization, usize, usize)>,
+}
+
+// U+FEFF at the very start of a file is a byte order mark, not text
+const BOM: &str = "\u{feff}";
+
+// Result of a single scan: the cleaned bytes plus the report on what was removed
+#[derive(Default)]
+pub struct NonAsciiScan {
+    pub filtered: Vec<u8>,
+    pub report: Report,
+}
+
+impl Report {
+    // Whether anything at all was found, which is what decides a rewrite
+    pub fn is_dirty(&self) -> bool {
+        !self.non_ascii_positions.is_empty()
+            || !self.invisible_positions.is_empty()
+            || !self.control_positions.is_empty()
+            || !self.denied_positions.is_empty()
+            || !self.skipped_lines.is_empty()
+            || self.converted_line_endings > 0
+            || self.normalized_spaces > 0
+            || self.bom == Some(Bom::Strip)
+    }
+
+    // Share of the input made of non-ASCII bytes, from 0 to 100
+    pub fn non_ascii_percent(&self) -> f64 {
+        match self.input_bytes {
+            0 => 0.0,
+            total => self.non_ascii_bytes.len() as f64 * 100.0 / total as f64,
+        }
+    }
+
+    // Removed characters, invisible ones included, grouped into contiguous runs
+    pub fn runs(&self) -> Vec<Run> {
+        let mut positions: Vec<&Position> = self
+            .non_ascii_positions
+            .iter()
+            .chain(&self.invisible_positions)
+            .collect();
+        positions.sort_by_key(|p| p.offset);
+        contiguous(positions)
+    }
+
+    // `non_ascii_positions` alone grouped into runs, e.g. one per accented word
+    pub fn non_ascii_runs(&self) -> Vec<Run> {
+        contiguous(self.non_ascii_positions.iter().collect())
+    }
+
+    // `skipped_lines` as blocks of adjacent lines, a block identical to the
+    // one before it counted as a repeat of that one
+    pub fn watermark_blocks(&self) -> Vec<WatermarkBlock<'_>> {
+        let same = |a: &[SkippedLine], b: &[SkippedLine]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.text == b.text)
+        };
+        let mut blocks: Vec<WatermarkBlock> = Vec::new();
+        for adjacent in self.skipped_lines.chunk_by(|a, b| b.line == a.line + 1) {
+            let mut rest = adjacent;
+            while !rest.is_empty() {
+                if let Some(block) = blocks.last_mut()
+                    && rest.len() >= block.lines.len()
+                    && same(block.lines, &rest[..block.lines.len()])
+                {
+                    block.starts.push(rest[0].line);
+                    rest = &rest[block.lines.len()..];
+                    continue;
+                }
+                // A banner repeated without a gap ends where its repeat begins
+                let len = (1..=(rest.len() / 2).min(MAX_BANNER_LINES))
+                    .find(|&len| same(&rest[..len], &rest[len..2 * len]))
+                    .unwrap_or(rest.len());
+                blocks.push(WatermarkBlock {
+                    lines: &rest[..len],
+                    starts: vec![rest[0].line],
+                });
+                rest = &rest[len..];
+            }
+        }
+        blocks
+    }
+
+    // How many removed or kept watermark lines each pattern matched
+    pub fn watermarks_by_pattern(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for found in self.skipped_lines.iter().chain(&self.kept_watermarks) {
+            *counts.entry(found.pattern.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    // `char_counts` ordered from the most to the least frequent character
+    pub fn frequent_chars(&self) -> Vec<(char, usize)> {
+        let mut counts: Vec<_> = self.char_counts.iter().map(|(&ch, &n)| (ch, n)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+}
+
+// Positions in offset order merged wherever one ends where the next begins
+fn contiguous(positions: Vec<&Position>) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for p in positions {
+        let mut buf = [0; 4];
+        let encoded = p.ch.encode_utf8(&mut buf).as_bytes();
+        match runs.last_mut() {
+            Some(run) if run.offset + run.bytes.len() == p.offset => {
+                run.chars += 1;
+                run.bytes.extend_from_slice(encoded);
+            }
+            _ => runs.push(Run {
+                line: p.line,
+                column: p.column,
+                offset: p.offset,
+                chars: 1,
+                bytes: encoded.to_vec(),
+            }),
+        }
+    }
+    runs
+}
+
+// The first watermark found on `line`: the pattern, byte index and text
+fn find_watermark<'a>(line: &str, opts: &'a Options) -> Option<(&'a Pattern, usize, String)> {
+    if opts.whole_line_watermarks {
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        return opts.patterns.iter().find_map(|pattern| {
+            pattern
+                .regex
+                .find(trimmed)
+                .filter(|m| m.start() == 0 && m.end() == trimmed.len())
+                .map(|m| (pattern, indent, m.as_str().to_string()))
+        });
+    }
+
+    opts.patterns.iter().find_map(|pattern| {
+        pattern
+            .regex
+            .find(line)
+            .map(|m| (pattern, m.start(), m.as_str().to_string()))
+    })
+}
+
+// The pattern that makes `line` (without its terminator) a watermark under
+// `opts`, the same check the scan does. For linters that only need the
+// heuristic: `opts.patterns` holds the compiled set, `builtin_patterns` the
+// default one
+pub fn watermark_pattern<'a>(line: &str, opts: &'a Options) -> Option<&'a Pattern> {
+    find_watermark(line, opts).map(|(pattern, _, _)| pattern)
+}
+
+pub fn is_watermark(line: &str, opts: &Options) -> bool {
+    find_watermark(line, opts).is_some()
+}
+
+// Records the runs of at least `Options::zero_width_threshold` zero-width characters
+fn find_zero_width_runs(
+    line: &str,
+    line_no: usize,
+    start: usize,
+    opts: &Options,
+    found: &mut Vec<ZeroWidthRun>,
+) {
+    if line.is_ascii() {
+        return;
+    }
+    let mut run: Option<(usize, usize)> = None;
+    // A sentinel past the end closes a run that reaches the end of the line
+    let chars = line.char_indices().map(Some).chain([None]);
+    for item in chars {
+        match (item, run) {
+            (Some((idx, ch)), None) if is_zero_width(ch) => run = Some((idx, 1)),
+            (Some((_, ch)), Some((idx, len))) if is_zero_width(ch) => run = Some((idx, len + 1)),
+            (_, Some((idx, len))) => {
+                if len >= opts.zero_width_threshold {
+                    let text: String = line[idx..].chars().take(len).collect();
+                    found.push(ZeroWidthRun {
+                        line: line_no,
+                        column: opts.column_after(&line[..idx]),
+                        offset: start + idx,
+                        text,
+                    });
+                }
+                run = None;
+            }
+            (_, None) => {}
+        }
+    }
+}
+
+// Records the words of `line` whose letters come from more than one script
+fn find_mixed_scripts(line: &str, line_no: usize, opts: &Options, found: &mut Vec<MixedScript>) {
+    // Pure ASCII cannot mix scripts, which keeps the common case cheap
+    if line.is_ascii() {
+        return;
+    }
+
+    let mut rest = line;
+    while !rest.is_empty() {
+        let start = rest
+            .find(|c: char| c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        if let Some(scripts) = mixed_scripts(word) {
+            found.push(MixedScript {
+                line: line_no,
+                column: opts.column_after(&line[..line.len() - rest.len()]),
+                word: word.to_string(),
+                scripts,
+            });
+        }
+        rest = &rest[end..];
+    }
+}
+
+// Removes watermark lines and non-ASCII bytes from `data` without touching the filesystem
+pub fn scan_and_filter(data: &[u8]) -> NonAsciiScan {
+    scan_with_options(data, &Options::default())
+}
+
+// What becomes of a non-ASCII character that no allow list keeps
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action<'a> {
+    // Left in the output and out of the report, as if allowed
+    Keep,
+    // Removed and reported
+    Drop,
+    // Reported, with this text written in its place
+    Replace(Cow<'a, str>),
+}
+
+// The built-in policy: `replace_map`, then `transliterate`, then `replacement`,
+// else the character is dropped
+pub fn default_action(ch: char, opts: &Options) -> Action<'_> {
+    if let Some(replacement) = opts.replace_map.get(&ch) {
+        return Action::Replace(Cow::Borrowed(replacement));
+    }
+    if opts.transliterate
+        && let Some(ascii) = transliterate_with(ch, opts.translit_backend)
+    {
+        return Action::Replace(Cow::Borrowed(ascii));
+    }
+    match &opts.replacement {
+        Some(replacement) => Action::Replace(Cow::Borrowed(replacement)),
+        None => Action::Drop,
+    }
+}
+
+// Same as `scan_and_filter`, with the behaviour tuned by `opts`
+pub fn scan_with_options(data: &[u8], opts: &Options) -> NonAsciiScan {
+    scan_with(data, opts, |position| default_action(position.ch, opts))
+}
+
+// `scan_with_options` with the fate of every non-ASCII character that is not
+// allowed, denied or a stripped control decided by `visit`. Watermark lines
+// are removed before `visit` sees anything
+pub fn scan_with<'a, F>(data: &[u8], opts: &Options, mut visit: F) -> NonAsciiScan
+where
+    F: FnMut(&Position) -> Action<'a>,
+{
+    let mut filtered = Vec::with_capacity(data.len());
+    let mut report = Report {
+        input_bytes: data.len(),
+        ..Report::default()
+    };
+
+    let text = prepare(data, opts, &mut report);
+    // Byte offset of the current line, the same split as `str::lines`
+    let mut line_start = 0;
+    for (line_no, raw) in text.split_inclusive('\n').enumerate() {
+        scan_line(
+            raw,
+            line_no + 1,
+            line_start,
+            opts,
+            &mut visit,
+            &mut report,
+            &mut filtered,
+        );
+        line_start += raw.len();
+    }
+
+    NonAsciiScan { filtered, report }
+}
+
+// The text a scan works on: `data` decoded and normalized as `opts` asks
+fn prepare<'d>(data: &'d [u8], opts: &Options, report: &mut Report) -> Cow<'d, str> {
+    let (mut text, encoding) = decode(data, opts.detect_encoding);
+    if let Some(name) = encoding {
+        debug!("decoded {} bytes as {name}", data.len());
+    }
+    report.encoding = encoding;
+    if let Some(form) = opts.normalize {
+        let normalized = normalize(&text, form);
+        debug!(
+            "normalized to {}: {} bytes became {}",
+            form.name(),
+            text.len(),
+            normalized.len()
+        );
+        report.normalized = Some((form, text.len(), normalized.len()));
+        text = Cow::Owned(normalized);
+    }
+    text
+}
+
+fn normalize(text: &str, form: Normalization) -> String {
+    match form {
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfkc => text.nfkc().collect(),
+    }
+}
+
+// Filters one line, terminator included, that starts `start` bytes into the input
+fn scan_line<'a>(
+    raw: &str,
+    line_no: usize,
+    start: usize,
+    opts: &Options,
+    visit: &mut dyn FnMut(&Position) -> Action<'a>,
+    report: &mut Report,
+    filtered: &mut Vec<u8>,
+) {
+    let line = raw
+        .strip_suffix("\r\n")
+        .or_else(|| raw.strip_suffix('\n'))
+        .unwrap_or(raw);
+    let ending = &raw[line.len()..];
+    let mut terminator = opts.line_ending.terminator(ending);
+    if terminator != ending {
+        report.converted_line_endings += 1;
+    }
+    // Bytes of `raw` copied to the output untouched
+    let mut kept = 0;
+
+    // A byte order mark is not part of the text, whatever `opts.bom` does with it
+    let mut start = start;
+    let mut line = line;
+    if start == 0
+        && let Some(rest) = line.strip_prefix(BOM)
+    {
+        report.bom = Some(opts.bom);
+        if opts.bom == Bom::Keep {
+            filtered.extend_from_slice(BOM.as_bytes());
+            kept += BOM.len();
+        }
+        start += BOM.len();
+        line = rest;
+    }
+    let bom_kept = kept;
+    if terminator == ending {
+        kept += ending.len();
+    }
+    // All that survives of a watermark found only after cleaning
+    let bare = kept;
+
+    // Minified code and data blobs are not worth cleaning, or even scanning
+    if opts.is_too_long(line) {
+        trace!("line {line_no}: longer than the limit, left untouched");
+        report.long_lines.push(line_no);
+        if opts.record_lines {
+            report.line_fates.push(LineFate::TooLong);
+        }
+        filtered.extend_from_slice(line.as_bytes());
+        filtered.extend_from_slice(terminator.as_bytes());
+        count_change(report, raw.len(), kept + line.len());
+        return;
+    }
+
+    // Watermark lines are dropped entirely, or kept verbatim when auditing;
+    // exempted ones are cleaned like any other line
+    let exempt = opts.keeps_line(line);
+    if let Some((pattern, idx, mark)) = find_watermark(line, opts) {
+        let found = SkippedLine {
+            line: line_no,
+            column: opts.column_after(&line[..idx]),
+            mark,
+            text: line.to_string(),
+            pattern: pattern.name.clone(),
+        };
+        if exempt {
+            trace!(
+                "line {line_no}: watermark '{}' exempted by a kept line",
+                found.mark
+            );
+            report.exempted_watermarks.push(found);
+        } else {
+            trace!(
+                "line {line_no}: watermark '{}' matched by {}",
+                found.mark, found.pattern
+            );
+            let fate = if opts.strip_watermarks {
+                report.skipped_lines.push(found);
+                LineFate::Watermark
+            } else {
+                filtered.extend_from_slice(line.as_bytes());
+                kept += line.len();
+                report.kept_watermarks.push(found);
+                LineFate::WatermarkKept
+            };
+            if opts.record_lines {
+                report.line_fates.push(fate);
+            }
+            filtered.extend_from_slice(terminator.as_bytes());
+            count_change(report, raw.len(), kept);
+            return;
+        }
+    }
+
+    find_mixed_scripts(line, line_no, opts, &mut report.mixed_scripts);
+    if opts.zero_width_threshold > 0 {
+        find_zero_width_runs(line, line_no, start, opts, &mut report.zero_width_runs);
+    }
+
+    let mut stripped = 0;
+    let line_begin = filtered.len();
+    let mut soft_space: Option<usize> = None;
+    // Most lines of most files are plain ASCII that nothing removes, copied as is
+    if line.is_ascii() && !line.bytes().any(|b| opts.strips_ascii(b)) {
+        filtered.extend_from_slice(line.as_bytes());
+        kept += line.len();
+    } else {
+        let mut visual = 0;
+        for (idx, ch) in line.char_indices() {
+            let column = visual + 1;
+            visual = opts.advance(visual, ch == '\t');
+            if let Some(at) = soft_space.take()
+                && ch.is_whitespace()
+            {
+                drop_soft_space(filtered, at);
+            }
+            let mut buf = [0; 4];
+            let encoded = ch.encode_utf8(&mut buf).as_bytes();
+            let position = Position {
+                line: line_no,
+                column,
+                offset: start + idx,
+                ch,
+            };
+            // The deny list wins over everything that would keep the character
+            if !opts.deny.is_empty() && opts.deny.contains(&ch) {
+                stripped += 1;
+                report.denied_positions.push(position);
+                continue;
+            }
+            if opts.controls.strips(ch) {
+                stripped += 1;
+                report.control_positions.push(position);
+                continue;
+            }
+            if opts.respaces(ch) {
+                filtered.push(b' ');
+                report.normalized_spaces += 1;
+                continue;
+            }
+            if ch.is_ascii() || opts.allows(ch) {
+                filtered.extend_from_slice(encoded);
+                kept += encoded.len();
+                continue;
+            }
+            let action = visit(&position);
+            if action == Action::Keep {
+                filtered.extend_from_slice(encoded);
+                kept += encoded.len();
+                continue;
+            }
+
+            *report.char_counts.entry(ch).or_default() += 1;
+            stripped += 1;
+            if invisible_name(ch).is_some() {
+                report.invisible_positions.push(position);
+            } else {
+                report.non_ascii_positions.push(position);
+            }
+
+            let mut spelling = None;
+            if opts.transliterate && !opts.replace_map.contains_key(&ch) {
+                spelling = transliterate_with(ch, opts.translit_backend);
+                if spelling.is_none() {
+                    *report.untransliterated.entry(ch).or_default() += 1;
+                }
+            }
+            if let Action::Replace(text) = action {
+                // Counted when the replacement is what --translit would write
+                if spelling == Some(&*text) {
+                    report.transliterated += 1;
+                    // A word's trailing space is dropped again at the end of the
+                    // line or before whitespace
+                    if text.len() > 1 && text.ends_with(' ') {
+                        soft_space = Some(filtered.len() + text.len() - 1);
+                    }
+                }
+                filtered.extend_from_slice(text.as_bytes());
+            }
+            report.non_ascii_bytes.extend_from_slice(encoded);
+        }
+    }
+    if let Some(at) = soft_space {
+        drop_soft_space(filtered, at);
+    }
+
+    // Removing characters can complete a watermark, `@gen\u{200b}erated` say,
+    // which a second run would strip; it is handled now so one run is enough
+    if stripped > 0 && !exempt {
+        let cleaned = String::from_utf8_lossy(&filtered[line_begin..]).into_owned();
+        if let Some((pattern, idx, mark)) = find_watermark(&cleaned, opts) {
+            let found = SkippedLine {
+                line: line_no,
+                column: opts.column_after(&cleaned[..idx]),
+                mark,
+                text: line.to_string(),
+                pattern: pattern.name.clone(),
+            };
+            trace!(
+                "line {line_no}: watermark '{}' left after cleaning",
+                found.mark
+            );
+            if opts.strip_watermarks {
+                report.skipped_lines.push(found);
+                if opts.record_lines {
+                    report.line_fates.push(LineFate::Watermark);
+                }
+                filtered.truncate(line_begin);
+                filtered.extend_from_slice(terminator.as_bytes());
+                count_change(report, raw.len(), bare);
+                return;
+            }
+            report.kept_watermarks.push(found);
+        }
+    }
+
+    if opts.graphemes && stripped > 0 {
+        find_clusters(line, line_no, start, opts, &mut report.clusters);
+    }
+    // Removing what stood between a carriage return and the line feed leaves a
+    // CRLF ending, converted now so that a second run has nothing left to do
+    if stripped > 0 && ending == "\n" && filtered[line_begin..].ends_with(b"\r") {
+        let crlf = opts.line_ending.terminator("\r\n");
+        if crlf != "\r\n" {
+            filtered.pop();
+            kept = kept.saturating_sub(1);
+            report.converted_line_endings += 1;
+            terminator = crlf;
+        }
+    }
+    // A line that only had removed characters and whitespace is now blank
+    let emptied = stripped > 0 && filtered[line_begin..].iter().all(u8::is_ascii_whitespace);
+    if emptied {
+        report.emptied_lines.push(line_no);
+    }
+    if opts.record_lines {
+        report.line_fates.push(match stripped {
+            0 => LineFate::Kept,
+            n => LineFate::Stripped(n),
+        });
+    }
+    if emptied && opts.drop_emptied {
+        filtered.truncate(line_begin);
+        count_change(report, raw.len(), bom_kept);
+        return;
+    }
+    filtered.extend_from_slice(terminator.as_bytes());
+    count_change(report, raw.len(), kept);
+}
+
+fn count_change(report: &mut Report, len: usize, kept: usize) {
+    if kept < len {
+        report.changed_lines += 1;
+        report.changed_bytes += len - kept;
+    }
+}
+
+// Removes the space ending a transliterated word if nothing was written after it
+fn drop_soft_space(filtered: &mut Vec<u8>, at: usize) {
+    if filtered.len() == at + 1 {
+        filtered.pop();
+    }
+}
+
+// Whether `scan_with_options` would return anything but `data` itself. Nothing
+// is allocated for valid UTF-8 without --normalize, and the first line that
+// would change ends the search, so clean files are cheap to skip
+pub fn needs_cleaning(data: &[u8], opts: &Options) -> bool {
+    let (text, _) = decode(data, opts.detect_encoding);
+    if text.as_bytes() != data {
+        return true;
+    }
+    if let Some(form) = opts.normalize
+        && normalize(&text, form) != text
+    {
+        return true;
+    }
+    let text = match text.strip_prefix(BOM) {
+        Some(_) if opts.bom == Bom::Strip => return true,
+        Some(rest) => rest,
+        None => &text,
+    };
+
+    text.split_inclusive('\n').any(|raw| {
+        let line = raw
+            .strip_suffix("\r\n")
+            .or_else(|| raw.strip_suffix('\n'))
+            .unwrap_or(raw);
+        let ending = &raw[line.len()..];
+        if opts.line_ending.terminator(ending) != ending {
+            return true;
+        }
+        if opts.is_too_long(line) {
+            return false;
+        }
+        if !opts.keeps_line(line) && find_watermark(line, opts).is_some() {
+            return opts.strip_watermarks;
+        }
+        line.chars().any(|ch| {
+            opts.deny.contains(&ch)
+                || opts.controls.strips(ch)
+                || opts.respaces(ch)
+                || !(ch.is_ascii() || opts.allows(ch))
+        })
+    })
+}
+
+// Records every grapheme cluster of `line` that holds a removed non-ASCII character
+fn find_clusters(
+    line: &str,
+    line_no: usize,
+    start: usize,
+    opts: &Options,
+    found: &mut Vec<Cluster>,
+) {
+    let mut visual = 0;
+    for (idx, glyph) in line.grapheme_indices(true) {
+        let column = visual + 1;
+        visual = opts.advance(visual, glyph == "\t");
+        if glyph
+            .chars()
+            .any(|ch| !ch.is_ascii() && !opts.allows(ch) && !opts.deny.contains(&ch))
+        {
+            found.push(Cluster {
+                line: line_no,
+                column,
+                offset: start + idx,
+                text: glyph.to_string(),
+            });
+        }
+    }
+}
+
+// String in, string out: the cleaned text without the report, for callers that
+// only want the result. Filters exactly like `scan_with_options`
+pub fn filter_str(input: &str, opts: &Options) -> String {
+    let text = match opts.normalize {
+        Some(form) => Cow::Owned(normalize(input, form)),
+        None => Cow::Borrowed(input),
+    };
+
+    let mut report = Report::default();
+    let mut filtered = Vec::with_capacity(text.len());
+    let mut line_start = 0;
+    for (line_no, raw) in text.split_inclusive('\n').enumerate() {
+        scan_line(
+            raw,
+            line_no + 1,
+            line_start,
+            opts,
+            &mut |position: &Position| default_action(position.ch, opts),
+            &mut report,
+            &mut filtered,
+        );
+        line_start += raw.len();
+    }
+
+    // Only whole characters and replacement strings were copied
+    String::from_utf8(filtered).expect("filtered text is valid UTF-8")
+}
+
+// In-memory entry point for embedding: the cleaned bytes and the report on what was removed
+pub fn filter_bytes(data: &[u8], opts: &Options) -> (Vec<u8>, Report) {
+    let scan = scan_with_options(data, opts);
+    (scan.filtered, scan.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn positions(report: &Report) -> Vec<(usize, usize, char)> {
+        report
+            .non_ascii_positions
+            .iter()
+            .map(|p| (p.line, p.column, p.ch))
+            .collect()
+    }
+
+    #[test]
+    fn pure_ascii_is_untouched() {
+        let input = b"fn main() {\n    println!(\"hi\");\n}\n";
+        let scan = scan_and_filter(input);
+        assert_eq!(scan.filtered, input);
+        assert!(scan.report.non_ascii_positions.is_empty());
+        assert!(scan.report.skipped_lines.is_empty());
+        assert!(!scan.report.is_dirty());
+    }
+
+    #[test]
+    fn accented_text_is_stripped_with_positions() {
+        let scan = scan_and_filter("caf\u{e9}\nna\u{ef}ve r\u{e9}sum\u{e9}\n".as_bytes());
+        assert_eq!(scan.filtered, b"caf\nnave rsum\n");
+        assert_eq!(
+            positions(&scan.report),
+            vec![
+                (1, 4, '\u{e9}'),
+                (2, 3, '\u{ef}'),
+                (2, 8, '\u{e9}'),
+                (2, 12, '\u{e9}')
+            ]
+        );
+        assert_eq!(scan.report.non_ascii_bytes.len(), 8);
+        assert!(scan.report.skipped_lines.is_empty());
+    }
+
+    #[test]
+    fn watermark_lines_are_dropped() {
+        let scan = scan_and_filter(b"/// docs\nlet x = 1;\n  // Generated by a tool\n");
+        assert_eq!(scan.filtered, b"\nlet x = 1;\n\n");
+        assert!(scan.report.non_ascii_positions.is_empty());
+
+        let skipped: Vec<_> = scan
+            .report
+            .skipped_lines
+            .iter()
+            .map(|s| (s.line, s.column, s.mark.as_str(), s.text.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                (1, 1, "///", "/// docs"),
+                (3, 3, "// Generated by", "  // Generated by a tool")
+            ]
+        );
+    }
+
+    #[test]
+    fn watermarks_can_be_kept_for_auditing() {
+        let opts = Options {
+            strip_watermarks: false,
+            ..Options::default()
+        };
+        let input = "// SPDX-License-Identifier: MIT \u{a9}\nx\n";
+        let scan = scan_with_options(input.as_bytes(), &opts);
+        assert_eq!(scan.filtered, input.as_bytes());
+        assert!(scan.report.skipped_lines.is_empty());
+        assert_eq!(scan.report.kept_watermarks.len(), 1);
+        assert_eq!(scan.report.kept_watermarks[0].line, 1);
+        assert!(!scan.report.is_dirty());
+    }
+
+    #[test]
+    fn line_fates_are_recorded_on_request() {
+        let input = "keep\n/// doc\nna\u{ef}v\u{e9}\n";
+        assert!(
+            scan_and_filter(input.as_bytes())
+                .report
+                .line_fates
+                .is_empty()
+        );
+
+        let opts = Options {
+            record_lines: true,
+            ..Options::default()
+        };
+        let scan = scan_with_options(input.as_bytes(), &opts);
+        assert_eq!(
+            scan.report.line_fates,
+            vec![LineFate::Kept, LineFate::Watermark, LineFate::Stripped(2)]
+        );
+    }
+
+    #[test]
+    fn control_characters_are_stripped_on_request() {
+        let input = b"a\x0bb\tc\x7f\r\n";
+        assert_eq!(scan_and_filter(input).filtered, input);
+
+        let opts = Options {
+            controls: Controls::Strip,
+            ..Options::default()
+        };
+        let scan = scan_with_options(input, &opts);
+        assert_eq!(scan.filtered, b"ab\tc\r\n");
+        let found: Vec<_> = scan.report.control_positions.iter().map(|p| p.ch).collect();
+        assert_eq!(found, vec!['\x0b', '\x7f']);
+        assert!(scan.report.non_ascii_positions.is_empty());
+
+        let opts = Options {
+            controls: Controls::StripAll,
+            ..Options::default()
+        };
+        assert_eq!(scan_with_options(input, &opts).filtered, b"abc\r\n");
+    }
+
+    #[test]
+    fn allowed_ranges_bypass_the_filter() {
+        let ranges = parse_ranges("# Hebrew\n0590-05FF\n\nU+00E9\n").unwrap();
+        let opts = Options {
+            allow_ranges: ranges,
+            ..Options::default()
+        };
+        let input = "\u{5e9}\u{5dc}\u{5d5}\u{5dd} caf\u{e9} na\u{ef}ve";
+        let scan = scan_with_options(input.as_bytes(), &opts);
+        assert_eq!(
+            scan.filtered,
+            "\u{5e9}\u{5dc}\u{5d5}\u{5dd} caf\u{e9} nave".as_bytes()
+        );
+        assert_eq!(positions(&scan.report), vec![(1, 13, '\u{ef}')]);
+
+        let err = parse_ranges("0590-05FF\n9FFF-4E00\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(parse_ranges("zz").is_err());
+    }
+
+    #[test]
+    fn filter_str_matches_the_byte_scan() {
+        let input = "/// doc\ncaf\u{e9} \u{201c}ok\u{201d}\r\n";
+        let opts = Options {
+            replacement: Some("?".to_string()),
+            ..Options::default()
+        };
+        let cleaned = filter_str(input, &opts);
+        assert_eq!(cleaned, "\ncaf? ?ok?\r\n");
+        assert_eq!(
+            cleaned.as_bytes(),
+            scan_with_options(input.as_bytes(), &opts).filtered
+        );
+    }
+
+    #[test]
+    fn denied_characters_are_always_removed() {
+        let opts = Options {
+            deny: HashSet::from(['$', '\u{e9}']),
+            allow: HashSet::from(['\u{e9}', '\u{e8}']),
+            ..Options::default()
+        };
+        let scan = scan_with_options("$5 caf\u{e9} \u{e8}\u{ef}\n".as_bytes(), &opts);
+        assert_eq!(scan.filtered, "5 caf \u{e8}\n".as_bytes());
+        let denied: Vec<_> = scan.report.denied_positions.iter().map(|p| p.ch).collect();
+        assert_eq!(denied, vec!['$', '\u{e9}']);
+        assert_eq!(positions(&scan.report), vec![(1, 10, '\u{ef}')]);
+    }
+
+    #[test]
+    fn non_ascii_percent_is_relative_to_the_input() {
+        let scan = scan_and_filter("abcdef\u{e9}\u{e9}".as_bytes());
+        assert_eq!(scan.report.input_bytes, 10);
+        assert_eq!(scan.report.non_ascii_percent(), 40.0);
+        assert_eq!(scan_and_filter(b"").report.non_ascii_percent(), 0.0);
+    }
+
+    #[test]
+    fn empty_input() {
+        let scan = scan_and_filter(b"");
+        assert!(scan.filtered.is_empty());
+        assert!(scan.report.non_ascii_positions.is_empty());
+        assert!(scan.report.skipped_lines.is_empty());
+    }
+
+    #[test]
+    fn multibyte_characters_count_as_one_column() {
+        // Two, three and four byte sequences back to back, then ASCII again
+        let scan = scan_and_filter("a\u{e9}\u{20ac}\u{1f600}b".as_bytes());
+        assert_eq!(scan.filtered, b"ab");
+        assert_eq!(
+            positions(&scan.report),
+            vec![(1, 2, '\u{e9}'), (1, 3, '\u{20ac}'), (1, 4, '\u{1f600}')]
+        );
+        assert_eq!(scan.report.non_ascii_bytes.len(), 2 + 3 + 4);
+    }
+
+    #[test]
+    fn offsets_point_into_the_original_input() {
+        let input = "/// gone\r\n\u{e9}t\u{e9}\r\nx\u{200b}\n";
+        let scan = scan_and_filter(input.as_bytes());
+        let offsets: Vec<_> = scan
+            .report
+            .non_ascii_positions
+            .iter()
+            .chain(&scan.report.invisible_positions)
+            .map(|p| p.offset)
+            .collect();
+        assert_eq!(offsets, vec![10, 13, 18]);
+        for p in scan.report.non_ascii_positions.iter() {
+            assert!(input[p.offset..].starts_with(p.ch));
+        }
+    }
+
+    #[test]
+    fn line_endings_are_kept_or_converted() {
+        let input = b"a\r\nb\nc\r";
+        assert_eq!(scan_and_filter(input).filtered, input);
+
+        let opts = Options {
+            line_ending: LineEnding::Lf,
+            ..Options::default()
+        };
+        let scan = scan_with_options(input, &opts);
+        assert_eq!(scan.filtered, b"a\nb\nc\r");
+        assert_eq!(scan.report.converted_line_endings, 1);
+
+        let opts = Options {
+            line_ending: LineEnding::Crlf,
+            ..Options::default()
+        };
+        assert_eq!(scan_with_options(input, &opts).filtered, b"a\r\nb\r\nc\r");
+    }
+
+    #[test]
+    fn runs_group_adjacent_characters() {
+        let scan = scan_and_filter("\u{e9}\u{e8} x \u{200b}\u{fc}\ny\u{e0}".as_bytes());
+        let runs: Vec<_> = scan
+            .report
+            .runs()
+            .into_iter()
+            .map(|r| (r.line, r.column, r.offset, r.chars, r.bytes.len()))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![(1, 1, 0, 2, 4), (1, 6, 7, 2, 5), (2, 2, 14, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn normalization_composes_before_transliterating() {
+        let opts = Options {
+            normalize: Some(Normalization::Nfc),
+            transliterate: true,
+            ..Options::default()
+        };
+        let scan = scan_with_options("cafe\u{301}".as_bytes(), &opts);
+        assert_eq!(scan.filtered, b"cafe");
+        assert_eq!(positions(&scan.report), vec![(1, 4, '\u{e9}')]);
+        assert_eq!(scan.report.normalized, Some((Normalization::Nfc, 6, 5)));
+    }
+
+    #[test]
+    fn mixed_script_words_are_flagged() {
+        let scan = scan_and_filter(
+            "pay \u{440}\u{430}ypal \u{43f}\u{440}\u{438}\u{432}\u{435}\u{442}\n".as_bytes(),
+        );
+        let mixed = &scan.report.mixed_scripts;
+        assert_eq!(mixed.len(), 1);
+        assert_eq!((mixed[0].line, mixed[0].column), (1, 5));
+        assert_eq!(mixed[0].word, "\u{440}\u{430}ypal");
+        assert_eq!(mixed[0].scripts, vec![Script::Latin, Script::Cyrillic]);
+    }
+
+    #[test]
+    fn streaming_matches_the_in_memory_scan() {
+        let input = "/// doc\r\ncaf\u{e9} \u{200b}\n\u{440}\u{430}ypal\nlast";
+        let scan = scan_and_filter(input.as_bytes());
+
+        let mut streamed = Vec::new();
+        let report =
+            scan_reader(&mut input.as_bytes(), &mut streamed, &Options::default()).unwrap();
+        assert_eq!(streamed, scan.filtered);
+        assert_eq!(report.non_ascii_positions, scan.report.non_ascii_positions);
+        assert_eq!(report.invisible_positions, scan.report.invisible_positions);
+        assert_eq!(report.non_ascii_bytes, scan.report.non_ascii_bytes);
+        assert_eq!(report.skipped_lines.len(), scan.report.skipped_lines.len());
+        assert_eq!(report.mixed_scripts, scan.report.mixed_scripts);
+    }
+
+    #[test]
+    fn missing_final_newline_is_preserved() {
+        assert_eq!(scan_and_filter(b"a\nb").filtered, b"a\nb");
+    }
+
+    #[test]
+    fn disabled_builtin_pattern_keeps_its_lines() {
+        let mut opts = Options::default();
+        opts.patterns
+            .retain(|pattern| pattern.name != "doc-comment");
+        let input = "/// docs\n// @generated\nfn main() {}\n";
+        assert_eq!(filter_str(input, &opts), "/// docs\n\nfn main() {}\n");
+        assert_eq!(find_builtin("1"), Some("doc-comment"));
+        assert_eq!(find_builtin("spdx"), Some("spdx"));
+        assert_eq!(find_builtin("0"), None);
+        assert_eq!(find_builtin("nope"), None);
+    }
+
+    #[test]
+    fn skipped_lines_name_their_pattern() {
+        let input = "/// docs\n// @generated\n/// more\nfn main() {}\n";
+        let scan = scan_and_filter(input.as_bytes());
+        let names: Vec<_> = scan
+            .report
+            .skipped_lines
+            .iter()
+            .map(|s| s.pattern.as_str())
+            .collect();
+        assert_eq!(names, ["doc-comment", "at-generated", "doc-comment"]);
+        let counts = scan.report.watermarks_by_pattern();
+        assert_eq!(counts["doc-comment"], 2);
+        assert_eq!(counts["at-generated"], 1);
+    }
+
+    #[test]
+    fn needs_cleaning_agrees_with_the_full_scan() {
+        let inputs: [&[u8]; 8] = [
+            b"fn main() {}\n",
+            b"",
+            "caf\u{e9}\n".as_bytes(),
+            b"/// docs\nfn main() {}\n",
+            b"a\r\nb\r\n",
+            b"tab\there\x07\n",
+            b"caf\xe9 latin-1\n",
+            "e\u{301}\n".as_bytes(),
+        ];
+        let mut variants = vec![Options::default()];
+        variants.push(Options {
+            allow: HashSet::from(['\u{e9}']),
+            strip_watermarks: false,
+            ..Options::default()
+        });
+        variants.push(Options {
+            line_ending: LineEnding::Lf,
+            controls: Controls::Strip,
+            normalize: Some(Normalization::Nfc),
+            allow: HashSet::from(['\u{e9}']),
+            ..Options::default()
+        });
+        variants.push(Options {
+            deny: HashSet::from(['b']),
+            ..Options::default()
+        });
+
+        for opts in &variants {
+            for input in inputs {
+                let scan = scan_with_options(input, opts);
+                assert_eq!(
+                    needs_cleaning(input, opts),
+                    scan.filtered != input,
+                    "{:?}",
+                    String::from_utf8_lossy(input)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn builder_sets_the_same_fields_as_a_literal() {
+        let opts = Options::builder()
+            .replace("?")
+            .allow("\u{a9}\u{2192}")
+            .whole_line_watermarks(true)
+            .build();
+        assert_eq!(opts.replacement.as_deref(), Some("?"));
+        assert_eq!(opts.allow, HashSet::from(['\u{a9}', '\u{2192}']));
+        assert_eq!(
+            filter_str("\u{a9} na\u{ef}ve \u{2192}\n/// x\n", &opts),
+            "\u{a9} na?ve \u{2192}\n/// x\n"
+        );
+    }
+
+    #[test]
+    fn combining_marks_form_one_cluster() {
+        let opts = Options::builder().graphemes(true).build();
+        let scan = scan_with_options("man\u{303}ana e\u{301}\n".as_bytes(), &opts);
+        assert_eq!(scan.report.non_ascii_positions.len(), 2);
+        let clusters: Vec<_> = scan
+            .report
+            .clusters
+            .iter()
+            .map(|c| (c.column, c.text.as_str()))
+            .collect();
+        assert_eq!(clusters, [(3, "n\u{303}"), (8, "e\u{301}")]);
+        assert!(
+            scan_and_filter("e\u{301}\n".as_bytes())
+                .report
+                .clusters
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn watermark_predicate_matches_the_scan() {
+        let opts = Options::default();
+        assert!(is_watermark("/// docs", &opts));
+        assert!(!is_watermark("fn main() {}", &opts));
+        let pattern = watermark_pattern("    // @generated by a tool", &opts).unwrap();
+        assert_eq!(pattern.name, "at-generated");
+
+        let whole = Options::builder().whole_line_watermarks(true).build();
+        assert!(!is_watermark("/// docs", &whole));
+        assert!(is_watermark("  ///  ", &whole));
+    }
+
+    #[test]
+    fn replace_map_comes_before_the_fallback() {
+        let map = parse_replace_map(r#"{"\u201c": "\"", "U+201D": "\"", "\u2014": "--"}"#).unwrap();
+        let opts = Options {
+            replace_map: map,
+            replacement: Some("?".to_string()),
+            ..Options::default()
+        };
+        let input = "\u{201c}hi\u{201d} \u{2014} caf\u{e9}\n";
+        assert_eq!(filter_str(input, &opts), "\"hi\" -- caf?\n");
+        assert!(parse_replace_map(r#"{"ab": "x"}"#).is_err());
+    }
+
+    #[test]
+    fn emptied_lines_are_reported_and_optionally_dropped() {
+        let input = "keep\n\u{4f60}\u{597d}\n \u{2014} \n\nend\n";
+        let scan = scan_and_filter(input.as_bytes());
+        assert_eq!(scan.report.emptied_lines, [2, 3]);
+        assert_eq!(scan.filtered, b"keep\n\n  \n\nend\n");
+
+        let opts = Options {
+            drop_emptied: true,
+            ..Options::default()
+        };
+        assert_eq!(filter_str(input, &opts), "keep\n\nend\n");
+    }
+
+    #[test]
+    fn visitor_decides_the_fate_of_each_character() {
+        let mut seen = Vec::new();
+        let scan = scan_with(
+            "caf\u{e9} \u{2014} \u{4f60}\n".as_bytes(),
+            &Options::default(),
+            |p| {
+                seen.push((p.column, p.ch));
+                match p.ch {
+                    ch if (ch as u32) < 0x100 => Action::Keep,
+                    '\u{2014}' => Action::Replace("--".into()),
+                    _ => Action::Drop,
+                }
+            },
+        );
+        assert_eq!(scan.filtered, "caf\u{e9} -- \n".as_bytes());
+        assert_eq!(seen, [(4, '\u{e9}'), (6, '\u{2014}'), (8, '\u{4f60}')]);
+        let reported: Vec<_> = scan
+            .report
+            .non_ascii_positions
+            .iter()
+            .map(|p| p.ch)
+            .collect();
+        assert_eq!(reported, ['\u{2014}', '\u{4f60}']);
+    }
+
+    #[test]
+    fn deunicode_backend_romanizes_other_scripts() {
+        let opts = Options::builder()
+            .transliterate(true)
+            .translit_backend(TranslitBackend::Deunicode)
+            .build();
+        let scan = scan_with_options(
+            "\u{65e5}\u{672c}\n\u{41c}\u{43e}\u{441}\u{43a}\u{432}\u{430}\n".as_bytes(),
+            &opts,
+        );
+        assert_eq!(scan.filtered, b"Ri Ben\nMoskva\n");
+        assert_eq!(scan.report.transliterated, 8);
+
+        let opts = Options::builder().transliterate(true).replace("?").build();
+        let scan = scan_with_options("caf\u{e9} \u{65e5}\n".as_bytes(), &opts);
+        assert_eq!(scan.filtered, b"cafe ?\n");
+        assert_eq!(
+            scan.report.untransliterated,
+            BTreeMap::from([('\u{65e5}', 1)])
+        );
+    }
+
+    #[test]
+    fn zero_width_runs_at_the_threshold_are_watermarks() {
+        let text = "a\u{200b}\u{200c}b\nc\u{200b}\u{200d}\u{200b}\u{200c}\n";
+        let scan = scan_with_options(text.as_bytes(), &Options::default());
+        let runs = &scan.report.zero_width_runs;
+        assert_eq!(runs.len(), 1);
+        assert_eq!((runs[0].line, runs[0].column, runs[0].offset), (2, 2, 10));
+        assert_eq!(runs[0].text.chars().count(), 4);
+
+        let opts = Options::builder().zero_width_threshold(2).build();
+        let scan = scan_with_options(text.as_bytes(), &opts);
+        assert_eq!(scan.report.zero_width_runs.len(), 2);
+    }
+
+    #[test]
+    fn changed_lines_and_bytes_are_counted() {
+        let text = "ok\nna\u{ef}ve\n// @generated\nfine\r\n";
+        let scan = scan_with_options(text.as_bytes(), &Options::default());
+        assert_eq!(scan.report.changed_lines, 2);
+        assert_eq!(scan.report.changed_bytes, 2 + "// @generated".len());
+
+        let opts = Options::builder().line_ending(LineEnding::Lf).build();
+        let scan = scan_with_options(text.as_bytes(), &opts);
+        assert_eq!(scan.report.changed_lines, 3);
+        assert_eq!(scan.report.changed_bytes, 2 + "// @generated".len() + 2);
+    }
+
+    #[test]
+    fn exotic_spaces_become_plain_ones() {
+        let opts = Options::builder().normalize_spaces(true).build();
+        let text = "word\u{a0}word\u{3000}caf\u{e9}\n";
+        let scan = scan_with_options(text.as_bytes(), &opts);
+        assert_eq!(scan.filtered, b"word word caf\n");
+        assert_eq!(scan.report.normalized_spaces, 2);
+        assert_eq!(scan.report.non_ascii_positions.len(), 1);
+        assert!(needs_cleaning("a\u{2009}b".as_bytes(), &opts));
+    }
+
+    #[test]
+    fn a_second_run_changes_nothing() {
+        let corpus = [
+            "plain ascii\n",
+            "caf\u{e9} na\u{ef}ve\r\nno newline at the end \u{2014}",
+            "/// doc\n\u{e9}\n\n// @gen\u{200b}erated\n/\u{e9}// hidden doc\n",
+            "\u{65e5}\u{672c} text\r\n\u{feff}bom\rcr\x0b\n\u{a0}\u{a0}\n",
+            "e\u{301} and \u{2028} and \u{1f642}\n\r\n\n",
+            "a\r\u{200b}\n=\u{e9}  \r\u{202e}\u{301}\n",
+        ];
+        let options = [
+            Options::default(),
+            Options::builder().replace("?").build(),
+            Options::builder()
+                .transliterate(true)
+                .translit_backend(TranslitBackend::Deunicode)
+                .build(),
+            Options::builder()
+                .line_ending(LineEnding::Crlf)
+                .controls(Controls::StripAll)
+                .normalize_spaces(true)
+                .drop_emptied(true)
+                .build(),
+            Options::builder().line_ending(LineEnding::Lf).build(),
+            Options::builder()
+                .normalize(Normalization::Nfkc)
+                .strip_watermarks(false)
+                .build(),
+        ];
+        for opts in &options {
+            for text in corpus {
+                let once = scan_with_options(text.as_bytes(), opts).filtered;
+                let twice = scan_with_options(&once, opts).filtered;
+                assert_eq!(
+                    String::from_utf8_lossy(&twice),
+                    String::from_utf8_lossy(&once),
+                    "input {text:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn byte_order_mark_is_reported_not_filtered() {
+        let text = "\u{feff}caf\u{e9}\n";
+        let scan = scan_with_options(text.as_bytes(), &Options::default());
+        assert_eq!(scan.filtered, b"caf\n");
+        assert_eq!(scan.report.bom, Some(Bom::Strip));
+        assert!(scan.report.invisible_positions.is_empty());
+        assert_eq!(positions(&scan.report), [(1, 4, '\u{e9}')]);
+        assert_eq!(scan.report.non_ascii_positions[0].offset, 6);
+
+        let opts = Options::builder().bom(Bom::Keep).build();
+        let scan = scan_with_options("\u{feff}/// doc\nok\n".as_bytes(), &opts);
+        assert_eq!(scan.filtered, "\u{feff}\nok\n".as_bytes());
+        assert_eq!(scan.report.bom, Some(Bom::Keep));
+        assert!(!needs_cleaning("\u{feff}ok\n".as_bytes(), &opts));
+        assert!(needs_cleaning(
+            "\u{feff}ok\n".as_bytes(),
+            &Options::default()
+        ));
+    }
+
+    #[test]
+    fn long_lines_are_left_alone() {
+        let opts = Options::builder().skip_long_lines(8).build();
+        let text = "caf\u{e9}\n\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\n/// long doc line\n";
+        let scan = scan_with_options(text.as_bytes(), &opts);
+        let kept =
+            "caf\n\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\n/// long doc line\n";
+        assert_eq!(scan.filtered, kept.as_bytes());
+        assert_eq!(scan.report.long_lines, [2, 3]);
+        assert!(scan.report.skipped_lines.is_empty());
+        assert!(!needs_cleaning(
+            "\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}".as_bytes(),
+            &opts
+        ));
+    }
+
+    #[test]
+    fn aggregate_sums_the_scans() {
+        let mut total = AggregateReport::default();
+        for text in ["caf\u{e9}\n/// doc\n", "plain\n", "\u{e9}t\u{e9}\n"] {
+            total.add(&scan_and_filter(text.as_bytes()));
+        }
+        assert_eq!((total.files, total.changed), (3, 2));
+        assert_eq!((total.skipped_lines, total.non_ascii_bytes), (1, 6));
+        assert_eq!(total.char_counts, BTreeMap::from([('\u{e9}', 3)]));
+        assert_eq!(
+            total.to_string(),
+            "Processed 3 files, 2 changed: 1 watermark lines skipped, 6 non-ASCII bytes removed"
+        );
+    }
+
+    #[test]
+    fn non_ascii_runs_leave_invisible_characters_out() {
+        let scan = scan_and_filter("caf\u{e9}s \u{e9}\u{e8}\u{200b}\u{ea}\n\u{fc}".as_bytes());
+        let runs: Vec<_> = scan
+            .report
+            .non_ascii_runs()
+            .into_iter()
+            .map(|r| {
+                (
+                    r.line,
+                    r.column,
+                    r.chars,
+                    String::from_utf8(r.bytes).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                (1, 4, 1, "\u{e9}".to_string()),
+                (1, 7, 2, "\u{e9}\u{e8}".to_string()),
+                (1, 10, 1, "\u{ea}".to_string()),
+                (2, 1, 1, "\u{fc}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_line_exempts_matching_lines_from_watermark_removal() {
+        let opts = Options::builder().keep_line("=====").build();
+        let input = "// Generated by hand =====\n// Generated by a tool\n// caf\u{e9} =====\n";
+        let scan = scan_with_options(input.as_bytes(), &opts);
+        assert_eq!(
+            scan.filtered,
+            b"// Generated by hand =====\n\n// caf =====\n"
+        );
+        assert_eq!(scan.report.skipped_lines.len(), 1);
+        let exempted: Vec<_> = scan
+            .report
+            .exempted_watermarks
+            .iter()
+            .map(|w| w.line)
+            .collect();
+        assert_eq!(exempted, [1]);
+        assert!(!needs_cleaning(b"// Generated by hand =====\n", &opts));
+    }
+
+    #[test]
+    fn post_passes_report_what_they_changed() {
+        let passes = [
+            PostPass::TrimTrailingWhitespace,
+            PostPass::EnsureFinalNewline,
+        ];
+        let (data, changes) = post_process(b"a \t\r\nb\r\nc  ", &passes);
+        assert_eq!(&*data, b"a\r\nb\r\nc\r\n");
+        assert_eq!(changes, [(passes[0], true), (passes[1], true)]);
+
+        let (data, changes) = post_process(b"clean\n", &passes);
+        assert!(matches!(data, Cow::Borrowed(_)));
+        assert_eq!(changes, [(passes[0], false), (passes[1], false)]);
+    }
+
+    #[test]
+    fn repeated_watermark_banners_are_grouped() {
+        let banner = "// Generated by gen\n// Auto-generated, do not edit\n";
+        let input = format!("{banner}a\n{banner}b\n{banner}{banner}/// x\n");
+        let scan = scan_and_filter(input.as_bytes());
+        let blocks: Vec<_> = scan
+            .report
+            .watermark_blocks()
+            .into_iter()
+            .map(|block| (block.lines.len(), block.starts))
+            .collect();
+        assert_eq!(blocks, [(2, vec![1, 4, 7, 9]), (1, vec![11])]);
+    }
+
+    #[test]
+    fn tab_width_expands_tabs_in_columns() {
+        let input = "\t\u{e9}\ta\u{e8}\n\t/// x\n";
+        let columns = |opts: &Options| {
+            let scan = scan_with_options(input.as_bytes(), opts);
+            let chars: Vec<_> = scan
+                .report
+                .non_ascii_positions
+                .iter()
+                .map(|p| p.column)
+                .collect();
+            (chars, scan.report.skipped_lines[0].column)
+        };
+        assert_eq!(columns(&Options::default()), (vec![2, 5], 2));
+        assert_eq!(
+            columns(&Options::builder().tab_width(4).build()),
+            (vec![5, 10], 5)
+        );
+        // A width of 0 means no expansion rather than a division by zero
+        assert_eq!(
+            columns(&Options::builder().tab_width(0).build()),
+            (vec![2, 5], 2)
+        );
+    }
+
+    #[test]
+    fn chunked_scan_matches_serial_scan() {
+        let input =
+            "\u{feff}caf\u{e9}\r\n/// doc\nplain\n\u{2014}\n\u{43e}k a\u{200b}b\nend \u{1f642}";
+        let opts = Options::builder().record_lines(true).build();
+        let serial = scan_with_options(input.as_bytes(), &opts);
+        for chunks in [1, 2, 3, 7, 100] {
+            let chunked = scan_chunked(input.as_bytes(), &opts, chunks);
+            assert_eq!(chunked.filtered, serial.filtered, "{chunks} chunks");
+            let (a, b) = (&chunked.report, &serial.report);
+            assert_eq!(a.non_ascii_positions, b.non_ascii_positions);
+            assert_eq!(a.invisible_positions, b.invisible_positions);
+            assert_eq!(a.non_ascii_bytes, b.non_ascii_bytes);
+            assert_eq!(a.char_counts, b.char_counts);
+            assert_eq!(a.skipped_lines.len(), b.skipped_lines.len());
+            assert_eq!(a.skipped_lines[0].line, 2);
+            assert_eq!(a.emptied_lines, b.emptied_lines);
+            assert_eq!(a.mixed_scripts, b.mixed_scripts);
+            assert_eq!(a.line_fates, b.line_fates);
+            assert_eq!(a.bom, b.bom);
+            assert_eq!(a.input_bytes, b.input_bytes);
+            assert_eq!(
+                (a.changed_lines, a.changed_bytes),
+                (b.changed_lines, b.changed_bytes)
+            );
+        }
+    }
+}
@@ -21,129 +21,1301 @@
    OTHER DEALINGS IN THE SOFTWARE.
 */
 
+mod atomic;
+mod backup;
+mod cache;
+mod cli;
+mod config;
+mod git;
+mod gzip;
+mod hash;
+mod report;
+mod sarif;
+mod summary;
+mod walk;
+
+use atomic::{AtomicFile, write_atomic};
+use cache::{Cache, Lookup};
+use cli::ColorMode;
+use cli::{Args, EmptyAction, Input};
 use entropy::shannon_entropy;
-use sha256::digest;
+use flate2::write::GzEncoder;
+use hash::{HashingReader, HashingWriter};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{LevelFilter, debug, info};
+use rayon::prelude::*;
+use remove_water::{
+    AggregateReport, Report, invisible_name, post_process, scan_chunked, scan_reader,
+    scan_with_options,
+};
+use report::{FileReport, Palette};
 use std::env;
-use std::fs::{read, write};
-use std::str;
-
-// Watermarks to detect and remove
-static CODE_WATERMARKS: &[&str] = &[
-    "///",
-    "//!",
-    "/**",
-    "/*!",
-    "// Generated by",
-    "// Code generated by",
-    "// Auto-generated",
-    "// AI generated",
-    "// LLM",
-    "// @generated",
-    "// SPDX-License-Identifier:",
-];
-
-
-struct NonAsciiScan {
-    filtered: Vec<u8>,
-    non_ascii_positions: Vec<(usize, usize)>,
-    non_ascii_bytes: Vec<u8>,
-    watermark_positions: Vec<(usize, usize, String)>,
-}
-
-fn scan_and_filter(data: &[u8]) -> NonAsciiScan {
-    let mut filtered = Vec::with_capacity(data.len());
-    let mut non_ascii_positions = Vec::new();
-    let mut non_ascii_bytes = Vec::new();
-    let mut watermark_positions = Vec::new();
-
-    let text = String::from_utf8_lossy(data);
-    for (line_no, line) in text.lines().enumerate() {
-        let mut col = 1;
-        let mut skip = false;
-
-        // Should be rule files to include water marks to detect and remove
-        for &wm in CODE_WATERMARKS {
-            if let Some(idx) = line.find(wm) {
-                watermark_positions.push((line_no + 1, idx + 1, wm.to_string()));
-                // Mark this line as a watermark line to skip
-                skip = true;
-                break;
+use std::error::Error;
+use std::fs::{self, File, Metadata, OpenOptions, canonicalize, create_dir_all, metadata, read};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// How much of the start of a file the binary check looks at
+const BINARY_SNIFF_LEN: usize = 8192;
+
+// Read buffer of --stream, also what the binary check gets to see
+const STREAM_BUFFER: usize = 64 * 1024;
+
+// Smallest piece of a file --threads-per-file hands to a thread of its own
+const MIN_CHUNK: usize = 1 << 20;
+
+// Printed under a file that --verify read back clean
+const VERIFIED: &str = "Verified: read back clean and matching its digest.";
+
+// Why `data` looks like a binary file, judged from its first few KB
+fn binary_reason(data: &[u8]) -> Option<&'static str> {
+    let sample = &data[..data.len().min(BINARY_SNIFF_LEN)];
+    if sample.contains(&0) {
+        return Some("it contains NUL bytes");
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f)
+        .count();
+    // Text has next to no control bytes, so 10% is already far off
+    if control * 10 > sample.len() {
+        return Some("too many of its bytes are control characters");
+    }
+
+    None
+}
+
+// Process exit codes, documented in the usage text
+#[derive(Clone, Copy)]
+enum Exit {
+    Clean = 0,
+    Usage = 1,
+    Changed = 2,
+    IoError = 3,
+    BinarySkipped = 4,
+}
+
+impl Exit {
+    fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+// Per-file numbers that feed the batch summary
+#[derive(Default)]
+struct Outcome {
+    skipped_lines: usize,
+    non_ascii_bytes: usize,
+    changed_lines: usize,
+    changed_bytes: usize,
+    dirty: bool,
+    fails_check: bool,
+    changed: bool,
+    written: bool,
+    binary: bool,
+    exempt: bool,
+    // Findings collected for the --sarif log of the whole run
+    sarif: Vec<sarif::SarifResult>,
+    // What --extract collects of the file
+    extracted: Vec<u8>,
+    // The removed bytes, only kept for the --summary-json entropy
+    removed: Vec<u8>,
+    // What --cache should remember about a file found clean
+    cache: Option<cache::Entry>,
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+// Where --output puts the cleaned copy of `path`: the path itself, or inside it
+// at the source's relative location when it is a directory
+fn output_path(output: &Path, path: &str) -> PathBuf {
+    if !output.is_dir() {
+        return output.to_path_buf();
+    }
+    let relative: PathBuf = Path::new(path)
+        .components()
+        .filter(|part| matches!(part, Component::Normal(_)))
+        .collect();
+    output.join(relative)
+}
+
+fn report_path(args: &Args) -> String {
+    args.report_file
+        .as_deref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+// Appends what went to stdout in place of the per-file reports to --report-file
+fn record(report_file: &mut Option<BufWriter<File>>, data: &[u8]) -> io::Result<()> {
+    match report_file {
+        Some(file) => file.write_all(data),
+        None => Ok(()),
+    }
+}
+
+// The outcome of a file --cache allows to skip, still clean as of `entry`
+fn cached(
+    name: &str,
+    args: &Args,
+    entry: Option<cache::Entry>,
+    out: &mut dyn Write,
+) -> Result<Outcome, BoxError> {
+    if !args.quiet && !args.machine_output() {
+        writeln!(
+            out,
+            "{name}: clean and unchanged since the last run, skipped"
+        )?;
+    }
+    Ok(Outcome {
+        cache: entry,
+        ..Default::default()
+    })
+}
+
+// --verify: reads `target` back, which has to hash to `expected` and scan clean
+// under the same options; anything else means a bug or a concurrent writer
+fn verify(target: &Path, expected: &str, gz: bool, args: &Args) -> Result<(), BoxError> {
+    let file = File::open(target)?;
+    let source: Box<dyn Read> = match gz {
+        true => Box::new(gzip::decoder(file)),
+        false => Box::new(file),
+    };
+    let mut reader = BufReader::with_capacity(
+        STREAM_BUFFER,
+        HashingReader {
+            inner: source,
+            hasher: args.hash.hasher(),
+        },
+    );
+    let found = scan_reader(&mut reader, &mut io::sink(), &args.options)?;
+    let digest = reader.into_inner().hasher.finish();
+    debug!("{}: read back as {digest}", target.display());
+    if digest != expected {
+        return Err(format!(
+            "verification failed: {} on disk is {digest}, {expected} was written",
+            args.hash.name()
+        )
+        .into());
+    }
+    if found.is_dirty() {
+        return Err(format!(
+            "verification failed: the written file still has {} non-ASCII characters and {} watermark lines",
+            found.non_ascii_positions.len(),
+            found.skipped_lines.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// What --extract keeps of a scan: the removed non-ASCII bytes, followed by the
+// removed watermark lines under --extract-watermarks
+fn extracted(report: &Report, args: &Args) -> Vec<u8> {
+    let mut extracted = report.non_ascii_bytes.clone();
+    if args.extract_watermarks {
+        for skipped in &report.skipped_lines {
+            extracted.extend_from_slice(skipped.text.as_bytes());
+            extracted.push(b'\n');
+        }
+    }
+    extracted
+}
+
+// Whether `input` is a .gz file to clean the content of rather than its bytes
+fn gzipped(input: &Input, args: &Args) -> bool {
+    match input {
+        Input::File(path) => !args.no_decompress && gzip::is_gzip(Path::new(path)),
+        Input::Stdin => false,
+    }
+}
+
+// Atomically writes `data` to `path`, compressed again when it came from a .gz
+fn write_content(path: &Path, data: &[u8], gz: bool, keep_times: bool) -> io::Result<()> {
+    match gz {
+        true => write_atomic(path, &gzip::compress(data)?, keep_times),
+        false => write_atomic(path, data, keep_times),
+    }
+}
+
+// The outcome of a file an --exclude glob exempts from cleaning
+fn exempt(
+    name: &str,
+    pattern: &str,
+    args: &Args,
+    out: &mut dyn Write,
+) -> Result<Outcome, BoxError> {
+    debug!("{name}: matches --exclude '{pattern}'");
+    if !args.quiet && !args.machine_output() {
+        writeln!(out, "{name}: exempt by --exclude '{pattern}', skipped")?;
+    }
+    Ok(Outcome {
+        exempt: true,
+        ..Default::default()
+    })
+}
+
+fn process(
+    input: &Input,
+    args: &Args,
+    cache: Option<&Cache>,
+    multiple: bool,
+    palette: Palette,
+    out: &mut dyn Write,
+) -> Result<Outcome, BoxError> {
+    if let Input::File(path) = input
+        && let Some(pattern) = walk::excluded_by(path, &args.walk)
+    {
+        return exempt(args.input_name(input), pattern.as_str(), args, out);
+    }
+
+    // Files that were clean and kept their size and time are not even read
+    let mut known_digest = None;
+    let metadata = match (input, cache) {
+        (Input::File(path), Some(cache)) => {
+            let metadata = metadata(path)?;
+            match cache.lookup(path, &metadata) {
+                Lookup::Unchanged => {
+                    debug!("{path}: size and time match the cache, not read");
+                    let entry = cache.entry(path);
+                    return cached(args.input_name(input), args, entry, out);
+                }
+                Lookup::Modified(digest) => known_digest = Some(digest),
+                Lookup::Unknown => {}
             }
+            Some(metadata)
         }
+        _ => None,
+    };
 
-        if skip {
-            filtered.push(b'\n');
-            continue;
+    if args.stream {
+        return process_stream(input, args, metadata, multiple, palette, out);
+    }
+
+    let mut data = match input {
+        Input::Stdin => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            data
+        }
+        Input::File(path) => read(path)?,
+    };
+    let name = args.input_name(input);
+    debug!("{name}: read {} bytes", data.len());
+
+    // The scan sees what a .gz holds, a backup keeps the compressed original
+    let gz = gzipped(input, args);
+    let mut compressed = None;
+    if gz {
+        let plain = gzip::decompress(&data)?;
+        debug!("{name}: decompressed to {} bytes", plain.len());
+        compressed = Some(std::mem::replace(&mut data, plain));
+    }
+
+    if !args.force
+        && let Some(reason) = binary_reason(&data)
+    {
+        if !args.quiet {
+            let message = format!(
+                "Skipping {name}: looks like a binary file because {reason}. Use --force to clean it anyway."
+            );
+            writeln!(out, "{}", palette.warn(&message))?;
+        }
+        if let Input::Stdin = input
+            && !args.dry_run
+            && !args.check
+        {
+            io::stdout().write_all(&data)?;
         }
+        return Ok(Outcome {
+            binary: true,
+            ..Default::default()
+        });
+    }
+
+    let original_digest = args.hash.digest(&data);
+    // A touched file whose content is what the cache saw clean needs no scan
+    if let Some(metadata) = &metadata
+        && known_digest == Some(original_digest.as_str())
+    {
+        debug!("{name}: content matches the cache, not scanned");
+        let entry = cache::Entry::new(metadata, original_digest);
+        return cached(name, args, Some(entry), out);
+    }
 
-        for b in line.bytes() {
-            if b.is_ascii() {
-                filtered.push(b);
+    let result = match args.threads_per_file {
+        Some(threads) => {
+            let threads = if threads == 0 {
+                rayon::current_num_threads()
             } else {
-                non_ascii_positions.push((line_no + 1, col));
-                non_ascii_bytes.push(b);
+                threads
+            };
+            let chunks = threads.min(data.len() / MIN_CHUNK).max(1);
+            scan_chunked(&data, &args.options, chunks)
+        }
+        None => scan_with_options(&data, &args.options),
+    };
+    let (processed, post) = post_process(&result.filtered, &args.post);
+    let dirty = result.report.is_dirty() || post.iter().any(|&(_, changed)| changed);
+    info!(
+        "{name}: {} non-ASCII characters, {} watermark lines",
+        result.report.non_ascii_positions.len(),
+        result.report.skipped_lines.len()
+    );
+
+    // Cleaning down to whitespace alone is what --empty-action decides about
+    let blank = |data: &[u8]| data.iter().all(u8::is_ascii_whitespace);
+    let emptied = dirty && blank(&processed) && !blank(&data);
+    // What is written out, the input itself unless something needs cleaning
+    let filtered: &[u8] = match (emptied, &args.empty_action) {
+        _ if !dirty => &data,
+        (true, EmptyAction::Error) => {
+            return Err(
+                "cleaning would leave nothing but whitespace (--empty-action error)".into(),
+            );
+        }
+        // A skipped file keeps its content, so it is not changed either
+        (true, EmptyAction::Skip) => &data,
+        (true, EmptyAction::Truncate) => &[],
+        (true, EmptyAction::Newline) => b"\n",
+        _ => &processed,
+    };
+    let skipped = emptied && args.empty_action == EmptyAction::Skip;
+    if skipped && !args.quiet && !args.machine_output() && !args.check && !args.stats_only {
+        let message = format!(
+            "{name}: cleaning would leave nothing but whitespace, skipped (--empty-action skip)"
+        );
+        writeln!(out, "{}", palette.warn(&message))?;
+    }
+
+    let filtered_digest = args.hash.digest(filtered);
+    let changed = dirty && filtered_digest != original_digest;
+
+    let mut report = FileReport {
+        name,
+        result: &result.report,
+        hash: args.hash,
+        original_digest,
+        filtered_digest,
+        dirty,
+        changed,
+        written: false,
+        verified: false,
+        entropy: shannon_entropy(&result.report.non_ascii_bytes),
+        entropy_threshold: args.entropy_threshold,
+        max_non_ascii_percent: args.max_non_ascii_percent,
+        hex: args.hex,
+        per_char: args.per_char,
+        post,
+        limit: args.limit,
+        palette,
+    };
+    let original = Some((data.as_slice(), filtered));
+
+    // --interactive shows the report before anything is overwritten
+    let asks = asks(args, input, changed);
+    let approved = !asks || {
+        emit(out, args, multiple, &report, original, None, None)?;
+        confirm(out)?
+    };
+
+    let mut backup = None;
+    let mut copy = None;
+    report.written = match (input, &args.output) {
+        // The copy is written even for a clean file so the output is always complete
+        (_, Some(output)) => {
+            let target = match input {
+                Input::Stdin => output.to_path_buf(),
+                Input::File(path) => output_path(output, path),
+            };
+            if let Some(parent) = target.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                create_dir_all(parent)?;
+            }
+            debug!("{name}: writing {}", target.display());
+            write_content(&target, filtered, gz, false)?;
+            if args.verify {
+                verify(&target, &report.filtered_digest, gz, args)?;
+            }
+            copy = Some(target);
+            true
+        }
+        (Input::File(path), None) if changed && !args.dry_run && !args.check && approved => {
+            if args.backup {
+                let original = compressed.as_deref().unwrap_or(&data);
+                backup = Some(backup::write_backup(path, original)?);
+            }
+            debug!("{name}: writing {} bytes in place", filtered.len());
+            write_content(Path::new(path), filtered, gz, args.preserve_times)?;
+            if args.verify {
+                verify(Path::new(path), &report.filtered_digest, gz, args)?;
+            }
+            true
+        }
+        _ => false,
+    };
+    report.verified = args.verify && report.written;
+    let written = report.written;
+
+    if asks {
+        confirmed(out, &report, backup.as_deref(), multiple)?;
+    } else {
+        emit(
+            out,
+            args,
+            multiple,
+            &report,
+            original,
+            backup.as_deref(),
+            copy.as_deref(),
+        )?;
+    }
+
+    if let Input::Stdin = input
+        && !args.dry_run
+        && !args.check
+        && args.output.is_none()
+    {
+        io::stdout().write_all(filtered)?;
+    }
+
+    let cache = metadata
+        .filter(|_| !dirty)
+        .map(|metadata| cache::Entry::new(&metadata, report.original_digest.clone()));
+    Ok(Outcome {
+        skipped_lines: result.report.skipped_lines.len(),
+        non_ascii_bytes: result.report.non_ascii_bytes.len(),
+        changed_lines: result.report.changed_lines,
+        changed_bytes: result.report.changed_bytes,
+        dirty,
+        fails_check: report.fails_check(),
+        changed,
+        written,
+        binary: false,
+        exempt: false,
+        sarif: match args.sarif {
+            true => sarif::results(name, &result.report),
+            false => Vec::new(),
+        },
+        extracted: match args.extract {
+            Some(_) => extracted(&result.report, args),
+            None => Vec::new(),
+        },
+        removed: match args.summary_json {
+            true => result.report.non_ascii_bytes,
+            false => Vec::new(),
+        },
+        cache,
+    })
+}
+
+// Where streamed output goes; only a committed file replaces anything
+enum Sink {
+    Discard,
+    Stdout(io::Stdout),
+    File(AtomicFile),
+    Gzip(GzEncoder<AtomicFile>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Discard => Ok(buf.len()),
+            Sink::Stdout(stdout) => stdout.write(buf),
+            Sink::File(file) => file.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Discard => Ok(()),
+            Sink::Stdout(stdout) => stdout.flush(),
+            Sink::File(file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// `process` for --stream: the input is scanned line by line and never held in
+// memory, the cleaned lines going to a temporary file renamed over the target
+fn process_stream(
+    input: &Input,
+    args: &Args,
+    metadata: Option<Metadata>,
+    multiple: bool,
+    palette: Palette,
+    out: &mut dyn Write,
+) -> Result<Outcome, BoxError> {
+    let gz = gzipped(input, args);
+    let source: Box<dyn Read> = match input {
+        Input::Stdin => Box::new(io::stdin()),
+        Input::File(path) if gz => Box::new(gzip::decoder(File::open(path)?)),
+        Input::File(path) => Box::new(File::open(path)?),
+    };
+    let mut reader = BufReader::with_capacity(
+        STREAM_BUFFER,
+        HashingReader {
+            inner: source,
+            hasher: args.hash.hasher(),
+        },
+    );
+    let name = args.input_name(input);
+
+    let copy = match (input, &args.output) {
+        (Input::Stdin, Some(output)) => Some(output.to_path_buf()),
+        (Input::File(path), Some(output)) => Some(output_path(output, path)),
+        _ => None,
+    };
+    let sink = match (input, &copy) {
+        (_, Some(target)) => {
+            if let Some(parent) = target.parent()
+                && !parent.as_os_str().is_empty()
+            {
+                create_dir_all(parent)?;
+            }
+            Sink::File(AtomicFile::create(target)?)
+        }
+        _ if args.dry_run || args.check => Sink::Discard,
+        (Input::Stdin, None) => Sink::Stdout(io::stdout()),
+        (Input::File(path), None) => {
+            Sink::File(AtomicFile::create(Path::new(path))?.keep_times(args.preserve_times))
+        }
+    };
+    let sink = match sink {
+        Sink::File(file) if gz => Sink::Gzip(gzip::encoder(file)),
+        sink => sink,
+    };
+
+    let sample = reader.fill_buf()?;
+    if !args.force
+        && let Some(reason) = binary_reason(sample)
+    {
+        if !args.quiet {
+            let message = format!(
+                "Skipping {name}: looks like a binary file because {reason}. Use --force to clean it anyway."
+            );
+            writeln!(out, "{}", palette.warn(&message))?;
+        }
+        if let Sink::Stdout(mut stdout) = sink {
+            io::copy(&mut reader, &mut stdout)?;
+        }
+        return Ok(Outcome {
+            binary: true,
+            ..Default::default()
+        });
+    }
+
+    let mut writer = HashingWriter {
+        inner: BufWriter::new(sink),
+        hasher: args.hash.hasher(),
+    };
+    debug!("{name}: streaming");
+    let result = scan_reader(&mut reader, &mut writer, &args.options)?;
+    let original_digest = reader.into_inner().hasher.finish();
+    let filtered_digest = writer.hasher.finish();
+    let sink = writer.inner.into_inner().map_err(|err| err.into_error())?;
+    // The gzip trailer has to be in the file before it is renamed into place
+    let sink = match sink {
+        Sink::Gzip(encoder) => Sink::File(encoder.finish()?),
+        sink => sink,
+    };
+
+    let dirty = result.is_dirty();
+    let changed = dirty && filtered_digest != original_digest;
+    let mut report = FileReport {
+        name,
+        result: &result,
+        hash: args.hash,
+        original_digest,
+        filtered_digest,
+        dirty,
+        changed,
+        written: false,
+        verified: false,
+        entropy: shannon_entropy(&result.non_ascii_bytes),
+        entropy_threshold: args.entropy_threshold,
+        max_non_ascii_percent: args.max_non_ascii_percent,
+        hex: args.hex,
+        per_char: args.per_char,
+        post: Vec::new(),
+        limit: args.limit,
+        palette,
+    };
+
+    let asks = asks(args, input, changed);
+    let approved = !asks || {
+        emit(out, args, multiple, &report, None, None, None)?;
+        confirm(out)?
+    };
+
+    // A copy is always kept, the source only replaced when something changed
+    report.written = match sink {
+        Sink::File(file) if copy.is_some() || (changed && approved) => {
+            file.commit()?;
+            if args.verify
+                && let Some(target) = copy.as_deref().or(match input {
+                    Input::File(path) => Some(Path::new(path)),
+                    Input::Stdin => None,
+                })
+            {
+                verify(target, &report.filtered_digest, gz, args)?;
+            }
+            true
+        }
+        _ => false,
+    };
+    report.verified = args.verify && report.written;
+    let written = report.written;
+
+    if asks {
+        confirmed(out, &report, None, multiple)?;
+    } else {
+        emit(out, args, multiple, &report, None, None, copy.as_deref())?;
+    }
+
+    let cache = metadata
+        .filter(|_| !dirty)
+        .map(|metadata| cache::Entry::new(&metadata, report.original_digest.clone()));
+    Ok(Outcome {
+        skipped_lines: result.skipped_lines.len(),
+        non_ascii_bytes: result.non_ascii_bytes.len(),
+        changed_lines: result.changed_lines,
+        changed_bytes: result.changed_bytes,
+        dirty,
+        fails_check: report.fails_check(),
+        changed,
+        written,
+        binary: false,
+        exempt: false,
+        sarif: match args.sarif {
+            true => sarif::results(name, &result),
+            false => Vec::new(),
+        },
+        extracted: match args.extract {
+            Some(_) => extracted(&result, args),
+            None => Vec::new(),
+        },
+        removed: match args.summary_json {
+            true => result.non_ascii_bytes,
+            false => Vec::new(),
+        },
+        cache,
+    })
+}
+
+// Whether --interactive has to ask before `input` is overwritten
+fn asks(args: &Args, input: &Input, changed: bool) -> bool {
+    args.interactive && !args.yes && changed && matches!(input, Input::File(_))
+}
+
+// Asks on stdin whether to go ahead with a write, anything but y meaning no
+fn confirm(out: &mut dyn Write) -> io::Result<bool> {
+    write!(out, "Clean this file? [y/N] ")?;
+    out.flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// What became of a file after --interactive asked, its report already shown
+fn confirmed(
+    out: &mut dyn Write,
+    report: &FileReport,
+    backup: Option<&Path>,
+    multiple: bool,
+) -> io::Result<()> {
+    let palette = report.palette;
+    if let Some(backup) = backup {
+        writeln!(out, "Original saved to {}", backup.display())?;
+    }
+    match report.written {
+        true => writeln!(out, "{}", palette.good("File updated successfully."))?,
+        false => writeln!(out, "{}", palette.warn("Left unchanged."))?,
+    }
+    if report.verified {
+        writeln!(out, "{}", palette.good(VERIFIED))?;
+    }
+    if multiple {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// Prints the report of one input in the format selected on the command line.
+// `original` holds the input and filtered bytes, unavailable when streaming
+fn emit(
+    out: &mut dyn Write,
+    args: &Args,
+    multiple: bool,
+    report: &FileReport,
+    original: Option<(&[u8], &[u8])>,
+    backup: Option<&Path>,
+    copy: Option<&Path>,
+) -> io::Result<()> {
+    let name = report.name;
+    let palette = report.palette;
+    if args.quiet && !report.dirty {
+        // Clean files stay silent under --quiet
+        return Ok(());
+    }
+    if args.sarif {
+        // Everything goes into one log printed at the end of the run
+        return Ok(());
+    }
+    if args.print0 {
+        return report::print_nul(out, report);
+    }
+    if args.json {
+        return report::print_json(out, report, args.stats_only);
+    }
+    if args.check {
+        return report::print_check(out, report);
+    }
+    if args.diff_stat {
+        return report::print_diff_stat(out, report);
+    }
+    if args.stats_only {
+        return report::print_stats(out, report, multiple);
+    }
+
+    if multiple {
+        writeln!(out, "==> {name} <==")?;
+    }
+    report::print_text(out, report)?;
+    if let Some((data, filtered)) = original {
+        if let Some(lines) = args.context {
+            report::print_watermark_context(out, data, report.result, lines, palette)?;
+        }
+        if args.diff && report.changed {
+            writeln!(out)?;
+            report::print_diff(out, name, data, filtered, palette)?;
+        }
+    }
+    if let Some(backup) = backup {
+        writeln!(out, "\nOriginal saved to {}", backup.display())?;
+    }
+    if let Some(copy) = copy {
+        let message = format!("Cleaned copy written to {}", copy.display());
+        writeln!(out, "\n{}", palette.good(&message))?;
+    } else if report.written {
+        writeln!(out, "\n{}", palette.good("File updated successfully."))?;
+    } else if report.changed && args.dry_run && args.extract.is_none() {
+        let message =
+            "Dry run: file would be updated, nothing was written (pass --write to apply).";
+        writeln!(out, "\n{}", palette.warn(message))?;
+    }
+    if report.verified {
+        writeln!(out, "{}", palette.good(VERIFIED))?;
+    }
+    if multiple {
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+// `restore <file>...`: undoes a --backup run by moving the backups back
+fn restore(program: &str, paths: &[String]) -> ! {
+    if paths.is_empty() {
+        eprintln!("error: restore needs at least one file\nTry '{program} --help' for usage.");
+        Exit::Usage.exit();
+    }
+
+    let mut failed = false;
+    for path in paths {
+        match backup::restore_backup(path) {
+            Ok(backup) => println!("Restored {path} from {}", backup.display()),
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                failed = true;
+            }
+        }
+    }
+
+    match failed {
+        true => Exit::IoError.exit(),
+        false => Exit::Clean.exit(),
+    }
+}
+
+// Read-only gate for CI: lists every non-ASCII character and watermark of
+// `paths` as `path:line:column: finding` and fails if there is any
+fn validate(program: &str, rest: &[String]) -> ! {
+    let usage_error = |err: &str| -> ! {
+        eprintln!("error: {err}\nTry '{program} --help' for usage.");
+        Exit::Usage.exit();
+    };
+    // The config file and the selection and cleaning flags apply as they do to
+    // --check, so both agree on what counts as a finding
+    let argv: Vec<String> = std::iter::once(program.to_string())
+        .chain(rest.iter().cloned())
+        .collect();
+    let mut args = match cli::parse_args(&argv) {
+        Ok(Some(args)) => args,
+        Ok(None) => usage_error("validate needs at least one file"),
+        Err(err) => usage_error(&err),
+    };
+    if args.inputs.is_empty()
+        || args
+            .inputs
+            .iter()
+            .any(|input| matches!(input, Input::Stdin))
+    {
+        usage_error("validate needs at least one file");
+    }
+    if !args.dry_run || args.output.is_some() || args.backup || args.interactive {
+        usage_error("validate is read-only, drop --write, --output, --backup and --interactive");
+    }
+
+    let inputs = std::mem::take(&mut args.inputs);
+    let (files, mut errors) = walk::expand(inputs, &args.walk);
+    let mut offenders = 0;
+    let mut failed_files = 0;
+    let mut exempt = 0;
+    for input in &files {
+        let Input::File(path) = input else { continue };
+        if walk::excluded_by(path, &args.walk).is_some() {
+            exempt += 1;
+            continue;
+        }
+        let data = match read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                errors.push((path.clone(), err.to_string()));
+                continue;
             }
-            col += 1;
+        };
+        // A binary file cannot be vouched for, so it fails unless --force scans it
+        if !args.force
+            && let Some(reason) = binary_reason(&data)
+        {
+            println!(
+                "{path}: looks like a binary file because {reason}, add --force to validate it"
+            );
+            offenders += 1;
+            failed_files += 1;
+            continue;
         }
 
-        filtered.push(b'\n');
+        let report = scan_with_options(&data, &args.options).report;
+        let mut findings: Vec<(usize, usize, String)> = report
+            .non_ascii_positions
+            .iter()
+            .map(|p| {
+                let finding = format!("non-ASCII '{}' (U+{:04X})", p.ch, p.ch as u32);
+                (p.line, p.column, finding)
+            })
+            .collect();
+        findings.extend(report.invisible_positions.iter().map(|p| {
+            let name = invisible_name(p.ch).unwrap_or_default();
+            (
+                p.line,
+                p.column,
+                format!("invisible U+{:04X} {name}", p.ch as u32),
+            )
+        }));
+        if report.bom.is_some() {
+            findings.push((1, 1, "UTF-8 byte order mark".to_string()));
+        }
+        findings.extend(report.skipped_lines.iter().map(|skipped| {
+            let finding = format!("watermark '{}' ({})", skipped.mark, skipped.pattern);
+            (skipped.line, skipped.column, finding)
+        }));
+        findings.sort();
+
+        for (line, column, finding) in &findings {
+            println!("{path}:{line}:{column}: {finding}");
+        }
+        if !findings.is_empty() {
+            offenders += findings.len();
+            failed_files += 1;
+        }
     }
 
-    NonAsciiScan {
-        filtered,
-        non_ascii_positions,
-        non_ascii_bytes,
-        watermark_positions,
+    for (path, err) in &errors {
+        eprintln!("error: {path}: {err}");
+    }
+    if !errors.is_empty() {
+        Exit::IoError.exit();
+    }
+    let checked = files.len() - exempt;
+    match offenders {
+        0 => {
+            eprintln!("{checked} files are plain ASCII without watermarks");
+            Exit::Clean.exit()
+        }
+        n => {
+            eprintln!("{n} findings in {failed_files} of {checked} files");
+            Exit::Changed.exit()
+        }
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
     let argv: Vec<String> = env::args().collect();
-    if argv.len() != 2 {
-        println!(
-            "** Non-ASCII + Watermark Filter by Ehud (Udi) Shamir 2025 **\nusage: {} <source file>",
+    if argv.get(1).is_some_and(|arg| arg == "restore") {
+        restore(&argv[0], &argv[2..]);
+    }
+    if argv.get(1).is_some_and(|arg| arg == "validate") {
+        validate(&argv[0], &argv[2..]);
+    }
+    let mut args = match cli::parse_args(&argv) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", cli::usage(&argv[0]));
+            Exit::Clean.exit();
+        }
+        Err(err) => {
+            eprintln!("error: {err}\nTry '{} --help' for usage.", argv[0]);
+            Exit::Usage.exit();
+        }
+    };
+
+    // Diagnostics go through `log` to stderr, RUST_LOG overriding the -v count
+    let level = match args.verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+
+    // Reports are appended, so one file can keep the record of many runs
+    let mut report_file = match &args.report_file {
+        Some(path) => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("error: {}: {err}", path.display());
+                Exit::IoError.exit();
+            }
+        },
+        None => None,
+    };
+
+    let given = std::mem::take(&mut args.inputs);
+    // A directory or glob is a batch even when it turns out to hold one file
+    let batch = given.iter().any(|input| {
+        matches!(input, Input::File(path) if Path::new(path).is_dir() || walk::is_pattern(path))
+    });
+    let (mut inputs, walk_errors) = walk::expand(given, &args.walk);
+    // The cache lives among the files it describes but is not one of them
+    if let Some(cache) = args
+        .cache
+        .as_deref()
+        .and_then(|path| canonicalize(path).ok())
+    {
+        inputs.retain(|input| {
+            !matches!(input, Input::File(path) if canonicalize(path).is_ok_and(|path| path == cache))
+        });
+    }
+    let multiple = batch || inputs.len() > 1;
+    if multiple
+        && let Some(output) = &args.output
+        && !output.is_dir()
+    {
+        eprintln!(
+            "error: --output must be an existing directory when cleaning several files\nTry '{} --help' for usage.",
             argv[0]
         );
-        std::process::exit(0);
+        Exit::Usage.exit();
     }
 
-    let path = &argv[1];
-    let data = read(path)?;
-    let original_sha256 = digest(&data);
+    // Reports land on stderr when piping, so that is the stream to ask about
+    let palette = Palette {
+        enabled: match args.color {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                let terminal = match inputs.first() {
+                    Some(Input::Stdin) => io::stderr().is_terminal(),
+                    _ => io::stdout().is_terminal(),
+                };
+                !no_color && terminal && !args.machine_output()
+            }
+        },
+    };
+
+    // Verbose runs start by saying which watermark patterns are in effect
+    if args.options.record_lines && !args.machine_output() {
+        let mut listing = String::from("Active watermark patterns:\n");
+        for (n, pattern) in args.options.patterns.iter().enumerate() {
+            listing += &format!("  {:>2}. {:<24} {}\n", n + 1, pattern.name, pattern.regex);
+        }
+        match inputs.first() {
+            Some(Input::Stdin) => eprint!("{listing}"),
+            _ => print!("{listing}"),
+        }
+    }
+
+    // Long batch runs get a progress bar, kept off pipes and machine readable output
+    let show_progress = multiple
+        && io::stdout().is_terminal()
+        && !args.quiet
+        && !args.machine_output()
+        && !args.interactive;
+    let progress = match show_progress {
+        true => ProgressBar::new(inputs.len() as u64).with_style(
+            ProgressStyle::with_template("[{bar:40}] {pos}/{len} files {wide_msg}")
+                .expect("valid progress template")
+                .progress_chars("=> "),
+        ),
+        false => ProgressBar::hidden(),
+    };
+
+    // Reports are captured per file so parallel runs never interleave them.
+    // When piping, stdout carries the cleaned bytes so the report goes to stderr
+    let cache = args.cache.as_deref().map(|path| Cache::load(path, &args));
+
+    let stop = AtomicBool::new(args.fail_fast && !walk_errors.is_empty());
+    let run = |input: &Input| {
+        // Under --fail-fast files still queued after a failure are left alone
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut report = Vec::new();
+        let outcome = match input {
+            // Prompts have to follow the report they ask about right away
+            Input::File(_) if args.interactive => process(
+                input,
+                &args,
+                cache.as_ref(),
+                multiple,
+                palette,
+                &mut io::stdout(),
+            ),
+            _ => process(input, &args, cache.as_ref(), multiple, palette, &mut report),
+        };
+        if args.fail_fast && outcome.is_err() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        progress.set_message(args.input_name(input).to_string());
+        progress.inc(1);
+        // Only the cleaned bytes, the SARIF log, the summary or the extracted
+        // text may reach stdout, reports then go to stderr
+        let to_stderr = matches!(input, Input::Stdin)
+            || args.sarif
+            || args.summary_json
+            || args.extracts_to_stdout();
+        Some((
+            args.input_name(input).to_string(),
+            report,
+            to_stderr,
+            outcome,
+        ))
+    };
+
+    let results: Box<dyn Iterator<Item = _>> = match args.jobs {
+        Some(jobs) => {
+            let pool = match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+                Ok(pool) => pool,
+                Err(err) => {
+                    eprintln!("error: cannot start {jobs} worker threads: {err}");
+                    Exit::IoError.exit();
+                }
+            };
+            let mut done = pool.install(|| inputs.par_iter().filter_map(run).collect::<Vec<_>>());
+            done.sort_by(|a, b| a.0.cmp(&b.0));
+            Box::new(done.into_iter())
+        }
+        None => Box::new(inputs.iter().map_while(run)),
+    };
+
+    let mut outcomes = Vec::new();
+    let mut errors: Vec<(String, BoxError)> = walk_errors
+        .into_iter()
+        .map(|(path, err)| (path, err.into()))
+        .collect();
+
+    // A failing file is reported at the end instead of aborting the batch
+    let mut stdout = io::stdout();
+    let mut attempted = 0;
+    let mut cache_updates = Vec::new();
+    let mut summaries = Vec::new();
+    let mut removed = Vec::new();
+    for (name, report, to_stderr, outcome) in results {
+        attempted += 1;
+        let shown = progress.suspend(|| match to_stderr {
+            true => io::stderr().write_all(&report),
+            false => stdout.write_all(&report),
+        });
+        if let Err(err) = shown {
+            errors.push((name.clone(), err.into()));
+            if args.fail_fast {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        if let Some(file) = &mut report_file
+            && !report.is_empty()
+        {
+            // A single file's text report does not name it, the record has to
+            let header = !multiple && !args.json && !args.check && !args.stats_only;
+            let written = match header {
+                true => writeln!(file, "==> {name} <=="),
+                false => Ok(()),
+            }
+            .and_then(|_| file.write_all(&report::strip_colors(&report)));
+            if let Err(err) = written {
+                errors.push((report_path(&args), err.into()));
+            }
+        }
+        match outcome {
+            Ok(mut outcome) => {
+                if cache.is_some() {
+                    cache_updates.push((name.clone(), outcome.cache.take()));
+                }
+                if args.summary_json {
+                    removed.append(&mut outcome.removed);
+                    summaries.push(
+                        summary::FileSummary::new(
+                            &name,
+                            outcome.changed,
+                            outcome.written,
+                            outcome.binary,
+                        )
+                        .exempt(outcome.exempt)
+                        .counts(outcome.skipped_lines, outcome.non_ascii_bytes),
+                    );
+                }
+                outcomes.push(outcome)
+            }
+            Err(err) => errors.push((name, err)),
+        }
+    }
 
-    let result = scan_and_filter(&data);
-    let filtered_sha256 = digest(&result.filtered);
+    progress.finish_and_clear();
 
-    if !result.non_ascii_positions.is_empty() || !result.watermark_positions.is_empty() {
+    let summary =
+        multiple && !args.machine_output() && (!args.quiet || outcomes.iter().any(|o| o.dirty));
+    if summary && args.check {
         println!(
-            "Filtered {} non-ASCII characters, {} watermarks removed\nEntropy: {:.4}\nFiltered SHA256: {}",
-            result.non_ascii_bytes.len(),
-            result.watermark_positions.len(),
-            shannon_entropy(&result.non_ascii_bytes),
-            filtered_sha256
+            "Checked {} files, {} dirty",
+            outcomes.len(),
+            outcomes.iter().filter(|o| o.fails_check).count()
         );
+    } else if summary && args.diff_stat {
+        println!(
+            "{} files, {} lines and {} bytes {}",
+            outcomes.iter().filter(|o| o.changed).count(),
+            outcomes.iter().map(|o| o.changed_lines).sum::<usize>(),
+            outcomes.iter().map(|o| o.changed_bytes).sum::<usize>(),
+            if args.dry_run {
+                "would change"
+            } else {
+                "changed"
+            },
+        );
+    } else if summary {
+        // Only files actually written count as changed outside a dry run
+        let totals = AggregateReport {
+            files: outcomes.len(),
+            changed: outcomes
+                .iter()
+                .filter(|o| o.written || (args.dry_run && o.changed))
+                .count(),
+            skipped_lines: outcomes.iter().map(|o| o.skipped_lines).sum(),
+            non_ascii_bytes: outcomes.iter().map(|o| o.non_ascii_bytes).sum(),
+            ..AggregateReport::default()
+        };
+        match args.dry_run {
+            true => println!("{totals} (dry run, nothing was written)"),
+            false => println!("{totals}"),
+        }
+    }
 
-        if !result.non_ascii_positions.is_empty() {
-            println!("\nNon-ASCII positions:");
-            for (line, col) in &result.non_ascii_positions {
-                println!("  line {line}, col {col}");
-            }
+    let binary = outcomes.iter().filter(|o| o.binary).count();
+    if multiple && binary > 0 && !args.machine_output() && !args.quiet {
+        println!("Skipped {binary} binary files, use --force to include them");
+    }
+    let exempt = outcomes.iter().filter(|o| o.exempt).count();
+    if multiple && exempt > 0 && !args.machine_output() && !args.quiet {
+        println!("Skipped {exempt} files exempt by --exclude");
+    }
+
+    if args.sarif {
+        let results: Vec<_> = outcomes
+            .iter_mut()
+            .flat_map(|o| o.sarif.drain(..))
+            .collect();
+        let mut log = Vec::new();
+        if let Err(err) = sarif::print(&mut log, &results).and_then(|_| stdout.write_all(&log)) {
+            errors.push(("-".to_string(), err.into()));
         }
+        if let Err(err) = record(&mut report_file, &log) {
+            errors.push((report_path(&args), err.into()));
+        }
+    }
 
-        if !result.watermark_positions.is_empty() {
-            println!("\nRemoved watermarks:");
-            for (line, col, mark) in &result.watermark_positions {
-                println!("  line {line}, col {col}: {}", mark);
-            }
+    if let Some(mut cache) = cache {
+        for (name, entry) in cache_updates {
+            cache.update(name, entry);
+        }
+        if let Err(err) = cache.save() {
+            errors.push(("--cache".to_string(), err.into()));
+        }
+    }
+
+    if args.summary_json {
+        let mut summary = Vec::new();
+        let printed = summary::print(&mut summary, &summaries, &removed, errors.len())
+            .and_then(|_| stdout.write_all(&summary));
+        if let Err(err) = printed {
+            errors.push(("-".to_string(), err.into()));
         }
+        if let Err(err) = record(&mut report_file, &summary) {
+            errors.push((report_path(&args), err.into()));
+        }
+    }
 
-        if filtered_sha256 != original_sha256 {
-            write(path, &result.filtered)?;
-            println!("\nFile updated successfully.");
+    if let Some(path) = &args.extract {
+        let extracted: Vec<u8> = outcomes
+            .iter_mut()
+            .flat_map(|o| o.extracted.drain(..))
+            .collect();
+        let written = match args.extracts_to_stdout() {
+            true => stdout.write_all(&extracted).and_then(|_| stdout.flush()),
+            false => fs::write(path, &extracted),
+        };
+        if let Err(err) = written {
+            errors.push((path.display().to_string(), err.into()));
         }
-    } else {
-        println!("File is clean. No non-ASCII or watermark patterns detected.");
     }
 
-    Ok(())
+    if let Some(Err(err)) = report_file.as_mut().map(|file| file.flush()) {
+        errors.push((report_path(&args), err.into()));
+    }
+
+    for (name, err) in &errors {
+        eprintln!("error: {name}: {err}");
+    }
+    if attempted < inputs.len() {
+        eprintln!(
+            "Stopped after the first error, {} files were not processed",
+            inputs.len() - attempted
+        );
+    }
+
+    // The most serious outcome of the batch decides the code
+    let changed = if args.check {
+        outcomes.iter().any(|o| o.fails_check)
+    } else {
+        outcomes.iter().any(|o| o.changed)
+    };
+    let exit = if !errors.is_empty() {
+        Exit::IoError
+    } else if changed {
+        Exit::Changed
+    } else if binary > 0 {
+        Exit::BinarySkipped
+    } else {
+        Exit::Clean
+    };
+    exit.exit();
 }
@@ -0,0 +1,378 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::translit::TranslitBackend;
+use crate::watermark::{Pattern, builtin_patterns};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+
+// How line terminators are written back into the filtered output
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    // Each line keeps the terminator it had, `\n` or `\r\n`
+    #[default]
+    Keep,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    // Terminator to write for a line that originally ended in `original`
+    pub(crate) fn terminator(self, original: &str) -> &str {
+        match self {
+            _ if original.is_empty() => "",
+            LineEnding::Keep => original,
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+// Which ASCII control characters a scan removes; line feeds never are
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Controls {
+    #[default]
+    Keep,
+    // Everything but tabs and carriage returns
+    Strip,
+    StripAll,
+}
+
+impl Controls {
+    pub(crate) fn strips(self, ch: char) -> bool {
+        let control = ch.is_ascii_control() && ch != '\n';
+        match self {
+            Controls::Keep => false,
+            Controls::Strip => control && ch != '\t' && ch != '\r',
+            Controls::StripAll => control,
+        }
+    }
+}
+
+// What happens to a UTF-8 byte order mark at the start of the input
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Bom {
+    // Removed and reported as a BOM rather than as an invisible character
+    #[default]
+    Strip,
+    // Written back in front of the output
+    Keep,
+}
+
+// Unicode normalization form applied to the text before it is scanned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    Nfc,
+    Nfkc,
+}
+
+impl Normalization {
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::Nfc => "NFC",
+            Normalization::Nfkc => "NFKC",
+        }
+    }
+}
+
+// Every knob of a scan in one place, shared by the library entry points and the
+// binary, which fills it in from the command line. `Options::default()` removes
+// non-ASCII characters and the built-in watermarks and changes nothing else.
+// `Options::builder()` sets the fields one call at a time.
+#[derive(Clone)]
+pub struct Options {
+    // Substituted for each non-ASCII character; `None` deletes it
+    pub replacement: Option<String>,
+    // Text substituted for specific characters, ahead of `transliterate` and
+    // `replacement`
+    pub replace_map: HashMap<char, String>,
+    // Map accented letters and smart punctuation to ASCII before filtering
+    pub transliterate: bool,
+    // Table `transliterate` uses
+    pub translit_backend: TranslitBackend,
+    // Lines matching any of these are watermarks; defaults to `builtin_patterns`
+    pub patterns: Vec<Pattern>,
+    // Only treat a line as a watermark when a pattern covers the whole trimmed line
+    pub whole_line_watermarks: bool,
+    // Remove watermark lines; when false they are only reported
+    pub strip_watermarks: bool,
+    // Lines containing any of these are never watermarks, e.g. a `=====` divider
+    pub keep_lines: Vec<String>,
+    // Count a tab as reaching the next multiple of this many columns, the way
+    // editors show it; `None` counts it as one column like any character
+    pub tab_width: Option<NonZeroUsize>,
+    // Decode non-UTF-8 input (Latin-1, UTF-16, ...) properly instead of lossily
+    pub detect_encoding: bool,
+    // Compose combining marks (and with NFKC fold compatibility forms) first
+    pub normalize: Option<Normalization>,
+    // Non-ASCII characters that are kept as they are and left out of the report
+    pub allow: HashSet<char>,
+    // Always removed, even when ASCII or allowed
+    pub deny: HashSet<char>,
+    // Whole blocks of kept characters, e.g. all of CJK
+    pub allow_ranges: Vec<RangeInclusive<char>>,
+    // Fate of a leading byte order mark, see `Report::bom`
+    pub bom: Bom,
+    // Terminator written after each line, converting `\r\n` and `\n`
+    pub line_ending: LineEnding,
+    // ASCII control characters to remove, none by default
+    pub controls: Controls,
+    // Turn non-ASCII whitespace such as U+00A0 into a plain space instead of
+    // removing it, so the words around it stay apart
+    pub normalize_spaces: bool,
+    // Lines of more characters than this are left untouched and listed in
+    // `Report::long_lines`, for minified code and embedded blobs
+    pub skip_long_lines: Option<usize>,
+    // Remove lines the filter leaves blank instead of keeping an empty line
+    pub drop_emptied: bool,
+    // Fill `Report::line_fates` with what happened to every line
+    pub record_lines: bool,
+    // Also group removed characters into `Report::clusters`, one per glyph
+    pub graphemes: bool,
+    // Runs of at least this many zero-width characters are reported as
+    // `Report::zero_width_runs`; 0 turns the detector off
+    pub zero_width_threshold: usize,
+}
+
+impl Options {
+    // Starts from `Options::default()`
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder {
+            options: Options::default(),
+        }
+    }
+
+    // Whether the ASCII byte `b` is removed by `deny` or `controls`
+    pub(crate) fn strips_ascii(&self, b: u8) -> bool {
+        let ch = b as char;
+        self.controls.strips(ch) || (!self.deny.is_empty() && self.deny.contains(&ch))
+    }
+
+    // Whether `line` is beyond `skip_long_lines`; the byte length is an upper
+    // bound of the character count and nearly always settles it
+    pub(crate) fn is_too_long(&self, line: &str) -> bool {
+        self.skip_long_lines
+            .is_some_and(|max| line.len() > max && line.chars().count() > max)
+    }
+
+    // Whether `ch` becomes a plain space under `normalize_spaces`
+    pub(crate) fn respaces(&self, ch: char) -> bool {
+        self.normalize_spaces && !ch.is_ascii() && ch.is_whitespace()
+    }
+
+    // The 0-based `column` moved past one more character or glyph
+    pub(crate) fn advance(&self, column: usize, tab: bool) -> usize {
+        match self.tab_width {
+            Some(width) if tab => column + width.get() - column % width,
+            _ => column + 1,
+        }
+    }
+
+    // The 1-based column of whatever follows `before` on its line
+    pub(crate) fn column_after(&self, before: &str) -> usize {
+        before
+            .chars()
+            .fold(0, |column, ch| self.advance(column, ch == '\t'))
+            + 1
+    }
+
+    // Whether `line` is exempt from watermark removal by `keep_lines`
+    pub(crate) fn keeps_line(&self, line: &str) -> bool {
+        self.keep_lines
+            .iter()
+            .any(|keep| line.contains(keep.as_str()))
+    }
+
+    // Whether `ch` is kept by `allow` or `allow_ranges`
+    pub fn allows(&self, ch: char) -> bool {
+        self.allow.contains(&ch) || self.allow_ranges.iter().any(|range| range.contains(&ch))
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            replacement: None,
+            replace_map: HashMap::new(),
+            transliterate: false,
+            translit_backend: TranslitBackend::Simple,
+            patterns: builtin_patterns().expect("built-in watermark patterns compile"),
+            whole_line_watermarks: false,
+            strip_watermarks: true,
+            keep_lines: Vec::new(),
+            tab_width: None,
+            detect_encoding: false,
+            normalize: None,
+            allow: HashSet::new(),
+            deny: HashSet::new(),
+            allow_ranges: Vec::new(),
+            bom: Bom::Strip,
+            line_ending: LineEnding::Keep,
+            controls: Controls::Keep,
+            normalize_spaces: false,
+            skip_long_lines: None,
+            drop_emptied: false,
+            record_lines: false,
+            graphemes: false,
+            zero_width_threshold: 3,
+        }
+    }
+}
+
+// Chained setters for `Options`, each named after the field it fills in:
+//
+//     Options::builder().replace("?").allow("\u{a9}\u{2192}").whole_line_watermarks(true).build()
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    pub fn replace(mut self, replacement: &str) -> Self {
+        self.options.replacement = Some(replacement.to_string());
+        self
+    }
+
+    pub fn replace_char(mut self, ch: char, replacement: &str) -> Self {
+        self.options.replace_map.insert(ch, replacement.to_string());
+        self
+    }
+
+    pub fn transliterate(mut self, on: bool) -> Self {
+        self.options.transliterate = on;
+        self
+    }
+
+    pub fn translit_backend(mut self, backend: TranslitBackend) -> Self {
+        self.options.translit_backend = backend;
+        self
+    }
+
+    // Replaces the built-in watermark patterns
+    pub fn patterns(mut self, patterns: Vec<Pattern>) -> Self {
+        self.options.patterns = patterns;
+        self
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.options.patterns.push(pattern);
+        self
+    }
+
+    pub fn whole_line_watermarks(mut self, on: bool) -> Self {
+        self.options.whole_line_watermarks = on;
+        self
+    }
+
+    pub fn strip_watermarks(mut self, on: bool) -> Self {
+        self.options.strip_watermarks = on;
+        self
+    }
+
+    pub fn detect_encoding(mut self, on: bool) -> Self {
+        self.options.detect_encoding = on;
+        self
+    }
+
+    pub fn normalize(mut self, form: Normalization) -> Self {
+        self.options.normalize = Some(form);
+        self
+    }
+
+    // Tabs reach the next multiple of `width` columns, 0 counting them as one
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.options.tab_width = NonZeroUsize::new(width);
+        self
+    }
+
+    // Lines containing `substring` are cleaned but never dropped as watermarks
+    pub fn keep_line(mut self, substring: &str) -> Self {
+        self.options.keep_lines.push(substring.to_string());
+        self
+    }
+
+    // Every character of `chars` is added to the allow list
+    pub fn allow(mut self, chars: &str) -> Self {
+        self.options.allow.extend(chars.chars());
+        self
+    }
+
+    pub fn deny(mut self, chars: &str) -> Self {
+        self.options.deny.extend(chars.chars());
+        self
+    }
+
+    pub fn allow_range(mut self, range: RangeInclusive<char>) -> Self {
+        self.options.allow_ranges.push(range);
+        self
+    }
+
+    pub fn bom(mut self, bom: Bom) -> Self {
+        self.options.bom = bom;
+        self
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.options.line_ending = line_ending;
+        self
+    }
+
+    pub fn controls(mut self, controls: Controls) -> Self {
+        self.options.controls = controls;
+        self
+    }
+
+    pub fn normalize_spaces(mut self, on: bool) -> Self {
+        self.options.normalize_spaces = on;
+        self
+    }
+
+    pub fn skip_long_lines(mut self, max: usize) -> Self {
+        self.options.skip_long_lines = Some(max);
+        self
+    }
+
+    pub fn drop_emptied(mut self, on: bool) -> Self {
+        self.options.drop_emptied = on;
+        self
+    }
+
+    pub fn record_lines(mut self, on: bool) -> Self {
+        self.options.record_lines = on;
+        self
+    }
+
+    pub fn graphemes(mut self, on: bool) -> Self {
+        self.options.graphemes = on;
+        self
+    }
+
+    pub fn zero_width_threshold(mut self, threshold: usize) -> Self {
+        self.options.zero_width_threshold = threshold;
+        self
+    }
+
+    pub fn build(self) -> Options {
+        self.options
+    }
+}
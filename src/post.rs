@@ -0,0 +1,96 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::borrow::Cow;
+
+// A formatting pass run over the cleaned output, for `--post`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostPass {
+    // Spaces and tabs at the end of every line
+    TrimTrailingWhitespace,
+    // A newline after the last line, CRLF when the text already uses it
+    EnsureFinalNewline,
+}
+
+impl PostPass {
+    pub fn name(self) -> &'static str {
+        match self {
+            PostPass::TrimTrailingWhitespace => "trim-trailing-ws",
+            PostPass::EnsureFinalNewline => "ensure-final-newline",
+        }
+    }
+
+    // `data` after the pass, still borrowed if the pass changed nothing
+    pub fn apply(self, data: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            PostPass::TrimTrailingWhitespace => trim_trailing_whitespace(data),
+            PostPass::EnsureFinalNewline => ensure_final_newline(data),
+        }
+    }
+}
+
+// Runs `passes` over `data` in order, telling for each whether it changed anything
+pub fn post_process<'a>(
+    data: &'a [u8],
+    passes: &[PostPass],
+) -> (Cow<'a, [u8]>, Vec<(PostPass, bool)>) {
+    let mut data = Cow::Borrowed(data);
+    let mut changes = Vec::with_capacity(passes.len());
+    for &pass in passes {
+        let changed = match pass.apply(&data) {
+            Cow::Borrowed(_) => false,
+            Cow::Owned(after) => {
+                data = Cow::Owned(after);
+                true
+            }
+        };
+        changes.push((pass, changed));
+    }
+    (data, changes)
+}
+
+fn trim_trailing_whitespace(data: &[u8]) -> Cow<'_, [u8]> {
+    let mut trimmed = Vec::with_capacity(data.len());
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        let body = line.strip_suffix(b"\n").unwrap_or(line);
+        let body = body.strip_suffix(b"\r").unwrap_or(body);
+        let blanks = body.iter().rev().take_while(|&&b| b == b' ' || b == b'\t');
+        trimmed.extend_from_slice(&body[..body.len() - blanks.count()]);
+        trimmed.extend_from_slice(&line[body.len()..]);
+    }
+    match trimmed.len() == data.len() {
+        true => Cow::Borrowed(data),
+        false => Cow::Owned(trimmed),
+    }
+}
+
+fn ensure_final_newline(data: &[u8]) -> Cow<'_, [u8]> {
+    if data.is_empty() || data.ends_with(b"\n") {
+        return Cow::Borrowed(data);
+    }
+    let ending: &[u8] = match data.windows(2).any(|pair| pair == b"\r\n") {
+        true => b"\r\n",
+        false => b"\n",
+    };
+    Cow::Owned([data, ending].concat())
+}
@@ -0,0 +1,83 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+// A range file entry that is not a valid code point or range
+#[derive(Debug)]
+pub struct RangeError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid code point range on line {}: '{}' (expected e.g. 4E00-9FFF)",
+            self.line, self.text
+        )
+    }
+}
+
+impl Error for RangeError {}
+
+pub(crate) fn code_point(text: &str) -> Option<char> {
+    let hex = text.trim();
+    let hex = hex
+        .strip_prefix("U+")
+        .or_else(|| hex.strip_prefix("u+"))
+        .unwrap_or(hex);
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+// Parses one hex code point or `FIRST-LAST` range per line, e.g. `0590-05FF`
+// for Hebrew. Blank lines and `#` comments are skipped
+pub fn parse_ranges(text: &str) -> Result<Vec<RangeInclusive<char>>, RangeError> {
+    let mut ranges = Vec::new();
+
+    for (line_no, line) in text.lines().enumerate() {
+        let entry = line.split('#').next().unwrap_or_default().trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let range = match entry.split_once('-') {
+            Some((first, last)) => code_point(first).zip(code_point(last)),
+            None => code_point(entry).map(|ch| (ch, ch)),
+        };
+        match range {
+            Some((first, last)) if first <= last => ranges.push(first..=last),
+            _ => {
+                return Err(RangeError {
+                    line: line_no + 1,
+                    text: entry.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(ranges)
+}
@@ -0,0 +1,70 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::ranges::code_point;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+// A replacement map that is not a JSON object of strings, or has a key that
+// names no single character
+#[derive(Debug)]
+pub enum ReplaceMapError {
+    Json(serde_json::Error),
+    Key(String),
+}
+
+impl fmt::Display for ReplaceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplaceMapError::Json(err) => write!(f, "expected a JSON object of strings: {err}"),
+            ReplaceMapError::Key(key) => write!(
+                f,
+                "invalid key '{key}' (expected one character or a code point like U+201C)"
+            ),
+        }
+    }
+}
+
+impl Error for ReplaceMapError {}
+
+// Parses `{"“": "\"", "U+2014": "--"}`: each key is a character or its
+// code point, each value the text that replaces it
+pub fn parse_replace_map(text: &str) -> Result<HashMap<char, String>, ReplaceMapError> {
+    let entries: BTreeMap<String, String> =
+        serde_json::from_str(text).map_err(ReplaceMapError::Json)?;
+
+    entries
+        .into_iter()
+        .map(|(key, replacement)| {
+            let mut chars = key.chars();
+            let ch = match (chars.next(), chars.next()) {
+                (Some(ch), None) => Some(ch),
+                _ if key.starts_with(['U', 'u']) => code_point(&key),
+                _ => None,
+            };
+            ch.map(|ch| (ch, replacement))
+                .ok_or(ReplaceMapError::Key(key))
+        })
+        .collect()
+}
@@ -0,0 +1,1176 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::hash::HashAlgo;
+use owo_colors::OwoColorize;
+use remove_water::{
+    Bom, LineFate, Position, PostPass, Report, SkippedLine, control_name, invisible_name,
+    is_bidi_control,
+};
+use serde::Serialize;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// Shorter runs are ordinary accented words, not worth a hex dump
+const HEX_MIN_RUN: usize = 8;
+
+// Colours for terminal output, a no-op when disabled
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub enabled: bool,
+}
+
+impl Palette {
+    pub fn good(self, text: &str) -> String {
+        match self.enabled {
+            true => text.green().to_string(),
+            false => text.to_string(),
+        }
+    }
+
+    pub fn bad(self, text: &str) -> String {
+        match self.enabled {
+            true => text.red().to_string(),
+            false => text.to_string(),
+        }
+    }
+
+    pub fn warn(self, text: &str) -> String {
+        match self.enabled {
+            true => text.yellow().to_string(),
+            false => text.to_string(),
+        }
+    }
+}
+
+// Shannon entropy is measured in bits per byte, so 8 is uniformly random data
+pub const MAX_ENTROPY: f32 = 8.0;
+
+// Everything known about one processed input, shared by the text and JSON output
+pub struct FileReport<'a> {
+    pub name: &'a str,
+    pub result: &'a Report,
+    pub hash: HashAlgo,
+    pub original_digest: String,
+    pub filtered_digest: String,
+    pub dirty: bool,
+    pub changed: bool,
+    pub written: bool,
+    // Read back and found clean by --verify
+    pub verified: bool,
+    pub entropy: f32,
+    pub entropy_threshold: Option<f32>,
+    // --check fails above this share of non-ASCII bytes
+    pub max_non_ascii_percent: Option<f64>,
+    // Dump this many bytes of each long removed run, for --hex
+    pub hex: Option<usize>,
+    // List at most this many positions of each kind, for --limit
+    pub limit: Option<usize>,
+    // List removed characters one by one rather than in runs, for --per-char
+    pub per_char: bool,
+    pub palette: Palette,
+    // Each --post pass and whether it changed the cleaned content
+    pub post: Vec<(PostPass, bool)>,
+}
+
+impl FileReport<'_> {
+    // Entropy above the threshold hints at an encoded payload rather than prose
+    pub fn high_entropy(&self) -> bool {
+        self.entropy_threshold.is_some_and(|max| self.entropy > max)
+    }
+
+    fn shows_entropy(&self) -> bool {
+        self.entropy_threshold.is_none() || self.high_entropy()
+    }
+
+    // Mostly non-ASCII content is likely not text, cleaning it would destroy it
+    pub fn too_much_non_ascii(&self) -> bool {
+        self.max_non_ascii_percent
+            .is_some_and(|max| self.result.non_ascii_percent() > max)
+    }
+
+    // With a threshold, non-ASCII text alone fails --check only for high
+    // entropy or above the percentage limit
+    pub fn fails_check(&self) -> bool {
+        let result = self.result;
+        match (self.entropy_threshold, self.max_non_ascii_percent) {
+            _ if !self.dirty => false,
+            _ if self.too_much_non_ascii() => true,
+            (None, None) => true,
+            _ => {
+                self.high_entropy()
+                    || !result.invisible_positions.is_empty()
+                    || !result.control_positions.is_empty()
+                    || !result.denied_positions.is_empty()
+                    || !result.mixed_scripts.is_empty()
+                    || !result.zero_width_runs.is_empty()
+                    || !result.skipped_lines.is_empty()
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonPosition {
+    line: usize,
+    column: usize,
+    offset: usize,
+    char: char,
+}
+
+#[derive(Serialize)]
+struct JsonCharCount {
+    char: char,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct JsonInvisible {
+    line: usize,
+    column: usize,
+    offset: usize,
+    code_point: String,
+    name: &'static str,
+    bidi: bool,
+}
+
+#[derive(Serialize)]
+struct JsonControl {
+    line: usize,
+    column: usize,
+    offset: usize,
+    code_point: String,
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct JsonMixedScript<'a> {
+    line: usize,
+    column: usize,
+    word: &'a str,
+    scripts: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct JsonZeroWidthRun {
+    line: usize,
+    column: usize,
+    offset: usize,
+    length: usize,
+    code_points: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JsonRepeatedWatermark {
+    length: usize,
+    repeats: usize,
+    lines: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct JsonPostPass {
+    pass: &'static str,
+    changed: bool,
+}
+
+#[derive(Serialize)]
+struct JsonRun {
+    line: usize,
+    column: usize,
+    offset: usize,
+    length: usize,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct JsonCluster<'a> {
+    line: usize,
+    column: usize,
+    offset: usize,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonWatermark<'a> {
+    line: usize,
+    column: usize,
+    text: &'a str,
+    line_text: &'a str,
+    pattern: &'a str,
+}
+
+// Field names are part of the tool's interface, keep them stable
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    path: &'a str,
+    hash: &'static str,
+    original_digest: &'a str,
+    filtered_digest: &'a str,
+    // Kept from before --hash existed, only present for sha256
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_sha256: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filtered_sha256: Option<&'a str>,
+    changed: bool,
+    written: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    verified: bool,
+    skipped_lines: usize,
+    long_lines: usize,
+    non_ascii_chars: usize,
+    non_ascii_bytes: usize,
+    non_ascii_percent: f64,
+    transliterated: usize,
+    untransliterated: usize,
+    entropy: f32,
+    high_entropy: bool,
+    invisible_chars: usize,
+    control_chars: usize,
+    denied_chars: usize,
+    encoding: &'static str,
+    // "stripped" or "kept" when the file started with a byte order mark
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bom: Option<&'static str>,
+    kept_watermarks: usize,
+    exempted_watermarks: usize,
+    zero_width_watermarks: usize,
+    watermark_patterns: BTreeMap<&'a str, usize>,
+    converted_line_endings: usize,
+    normalized_spaces: usize,
+    changed_lines: usize,
+    changed_bytes: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    post_passes: Vec<JsonPostPass>,
+    // Per-finding lists, left out under --stats-only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    char_counts: Option<Vec<JsonCharCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    untransliterated_chars: Option<Vec<JsonCharCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    positions: Option<Vec<JsonPosition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runs: Option<Vec<JsonRun>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clusters: Option<Vec<JsonCluster<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invisible: Option<Vec<JsonInvisible>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    controls: Option<Vec<JsonControl>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    denied: Option<Vec<JsonPosition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emptied_lines: Option<&'a [usize]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mixed_scripts: Option<Vec<JsonMixedScript<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zero_width_runs: Option<Vec<JsonZeroWidthRun>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watermarks: Option<Vec<JsonWatermark<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeated_watermarks: Option<Vec<JsonRepeatedWatermark>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kept: Option<Vec<JsonWatermark<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exempted: Option<Vec<JsonWatermark<'a>>>,
+}
+
+fn bom_action(bom: Bom) -> &'static str {
+    match bom {
+        Bom::Strip => "stripped",
+        Bom::Keep => "kept",
+    }
+}
+
+fn code_points(text: &str) -> Vec<String> {
+    text.chars()
+        .map(|ch| format!("U+{:04X}", ch as u32))
+        .collect()
+}
+
+fn json_position(p: &Position) -> JsonPosition {
+    JsonPosition {
+        line: p.line,
+        column: p.column,
+        offset: p.offset,
+        char: p.ch,
+    }
+}
+
+fn json_watermark(found: &SkippedLine) -> JsonWatermark<'_> {
+    JsonWatermark {
+        line: found.line,
+        column: found.column,
+        text: &found.mark,
+        line_text: &found.text,
+        pattern: &found.pattern,
+    }
+}
+
+pub fn print_json(out: &mut dyn Write, report: &FileReport, stats_only: bool) -> io::Result<()> {
+    let result = report.result;
+    let mut json = JsonReport {
+        path: report.name,
+        hash: report.hash.name(),
+        original_digest: &report.original_digest,
+        filtered_digest: &report.filtered_digest,
+        original_sha256: (report.hash == HashAlgo::Sha256).then_some(&report.original_digest),
+        filtered_sha256: (report.hash == HashAlgo::Sha256).then_some(&report.filtered_digest),
+        changed: report.changed,
+        written: report.written,
+        verified: report.verified,
+        skipped_lines: result.skipped_lines.len(),
+        long_lines: result.long_lines.len(),
+        non_ascii_chars: result.non_ascii_positions.len(),
+        non_ascii_bytes: result.non_ascii_bytes.len(),
+        non_ascii_percent: result.non_ascii_percent(),
+        transliterated: result.transliterated,
+        untransliterated: result.untransliterated.values().sum(),
+        entropy: report.entropy,
+        high_entropy: report.high_entropy(),
+        invisible_chars: result.invisible_positions.len(),
+        control_chars: result.control_positions.len(),
+        denied_chars: result.denied_positions.len(),
+        encoding: result.encoding.unwrap_or("UTF-8"),
+        bom: result.bom.map(bom_action),
+        kept_watermarks: result.kept_watermarks.len(),
+        exempted_watermarks: result.exempted_watermarks.len(),
+        zero_width_watermarks: result.zero_width_runs.len(),
+        watermark_patterns: result.watermarks_by_pattern(),
+        converted_line_endings: result.converted_line_endings,
+        normalized_spaces: result.normalized_spaces,
+        changed_lines: result.changed_lines,
+        changed_bytes: result.changed_bytes,
+        post_passes: report
+            .post
+            .iter()
+            .map(|&(pass, changed)| JsonPostPass {
+                pass: pass.name(),
+                changed,
+            })
+            .collect(),
+        char_counts: Some(
+            result
+                .frequent_chars()
+                .into_iter()
+                .map(|(char, count)| JsonCharCount { char, count })
+                .collect(),
+        ),
+        untransliterated_chars: Some(
+            result
+                .untransliterated
+                .iter()
+                .map(|(&char, &count)| JsonCharCount { char, count })
+                .collect(),
+        ),
+        positions: Some(
+            result
+                .non_ascii_positions
+                .iter()
+                .map(json_position)
+                .collect(),
+        ),
+        runs: Some(
+            result
+                .non_ascii_runs()
+                .into_iter()
+                .map(|run| JsonRun {
+                    line: run.line,
+                    column: run.column,
+                    offset: run.offset,
+                    length: run.chars,
+                    text: String::from_utf8_lossy(&run.bytes).into_owned(),
+                })
+                .collect(),
+        ),
+        invisible: Some(
+            result
+                .invisible_positions
+                .iter()
+                .map(|p| JsonInvisible {
+                    line: p.line,
+                    column: p.column,
+                    offset: p.offset,
+                    code_point: format!("U+{:04X}", p.ch as u32),
+                    name: invisible_name(p.ch).unwrap_or_default(),
+                    bidi: is_bidi_control(p.ch),
+                })
+                .collect(),
+        ),
+        controls: Some(
+            result
+                .control_positions
+                .iter()
+                .map(|p| JsonControl {
+                    line: p.line,
+                    column: p.column,
+                    offset: p.offset,
+                    code_point: format!("U+{:04X}", p.ch as u32),
+                    name: control_name(p.ch).unwrap_or_default(),
+                })
+                .collect(),
+        ),
+        clusters: Some(
+            result
+                .clusters
+                .iter()
+                .map(|cluster| JsonCluster {
+                    line: cluster.line,
+                    column: cluster.column,
+                    offset: cluster.offset,
+                    text: &cluster.text,
+                })
+                .collect(),
+        ),
+        denied: Some(result.denied_positions.iter().map(json_position).collect()),
+        emptied_lines: Some(&result.emptied_lines),
+        mixed_scripts: Some(
+            result
+                .mixed_scripts
+                .iter()
+                .map(|mixed| JsonMixedScript {
+                    line: mixed.line,
+                    column: mixed.column,
+                    word: &mixed.word,
+                    scripts: mixed.scripts.iter().map(|script| script.name()).collect(),
+                })
+                .collect(),
+        ),
+        zero_width_runs: Some(
+            result
+                .zero_width_runs
+                .iter()
+                .map(|run| JsonZeroWidthRun {
+                    line: run.line,
+                    column: run.column,
+                    offset: run.offset,
+                    length: run.text.chars().count(),
+                    code_points: code_points(&run.text),
+                })
+                .collect(),
+        ),
+        watermarks: Some(result.skipped_lines.iter().map(json_watermark).collect()),
+        repeated_watermarks: Some(
+            result
+                .watermark_blocks()
+                .into_iter()
+                .filter(|block| block.starts.len() > 1)
+                .map(|block| JsonRepeatedWatermark {
+                    length: block.lines.len(),
+                    repeats: block.starts.len(),
+                    lines: block.starts,
+                })
+                .collect(),
+        ),
+        kept: Some(result.kept_watermarks.iter().map(json_watermark).collect()),
+        exempted: Some(
+            result
+                .exempted_watermarks
+                .iter()
+                .map(json_watermark)
+                .collect(),
+        ),
+    };
+
+    if stats_only {
+        json.char_counts = None;
+        json.untransliterated_chars = None;
+        json.positions = None;
+        json.runs = None;
+        json.clusters = None;
+        json.invisible = None;
+        json.controls = None;
+        json.denied = None;
+        json.emptied_lines = None;
+        json.mixed_scripts = None;
+        json.zero_width_runs = None;
+        json.watermarks = None;
+        json.repeated_watermarks = None;
+        json.kept = None;
+        json.exempted = None;
+    }
+
+    serde_json::to_writer(&mut *out, &json)?;
+    writeln!(out)
+}
+
+// The first `limit` of `items`, or all of them without a limit
+fn limited<T>(items: &[T], limit: Option<usize>) -> &[T] {
+    &items[..limit.unwrap_or(items.len()).min(items.len())]
+}
+
+// Says how many entries `limited` left out
+fn print_more(out: &mut dyn Write, total: usize, limit: Option<usize>) -> io::Result<()> {
+    match limit {
+        Some(limit) if total > limit => writeln!(out, "  (... and {} more)", total - limit),
+        _ => Ok(()),
+    }
+}
+
+pub fn print_text(out: &mut dyn Write, report: &FileReport) -> io::Result<()> {
+    let result = report.result;
+    let palette = report.palette;
+    if !report.dirty {
+        let audited = result.kept_watermarks.is_empty() && result.exempted_watermarks.is_empty();
+        let message = match audited {
+            true => "File is clean. No non-ASCII or watermark patterns detected.",
+            false => "File is clean. No non-ASCII characters detected.",
+        };
+        writeln!(out, "{}", palette.good(message))?;
+        print_kept_watermarks(out, result, palette)?;
+        return print_line_fates(out, result, palette);
+    }
+
+    let mut extra = String::new();
+    if !result.control_positions.is_empty() {
+        extra += &format!(", {} control characters", result.control_positions.len());
+    }
+    if !result.denied_positions.is_empty() {
+        extra += &format!(", {} denied characters", result.denied_positions.len());
+    }
+    writeln!(
+        out,
+        "Filtered {} non-ASCII characters{extra}, {} watermarks removed",
+        result.non_ascii_positions.len() + result.invisible_positions.len(),
+        result.skipped_lines.len()
+    )?;
+    let share = format!(
+        "Non-ASCII bytes: {} of {} ({:.2}%)",
+        result.non_ascii_bytes.len(),
+        result.input_bytes,
+        result.non_ascii_percent()
+    );
+    match report.too_much_non_ascii() {
+        true => writeln!(
+            out,
+            "{}",
+            palette.bad(&format!(
+                "{share}, above the {:.2}% limit",
+                report.max_non_ascii_percent.unwrap_or_default()
+            ))
+        )?,
+        false => writeln!(out, "{share}")?,
+    }
+    let entropy = format!(
+        "Entropy: {:.2} bits/byte (max {MAX_ENTROPY:.2})",
+        report.entropy
+    );
+    if report.high_entropy() {
+        writeln!(
+            out,
+            "{entropy}, above threshold {:.2}: possibly an encoded payload",
+            report.entropy_threshold.unwrap_or_default()
+        )?;
+    } else if report.shows_entropy() {
+        writeln!(out, "{entropy}")?;
+    }
+    writeln!(
+        out,
+        "Original {name}: {}\nFiltered {name}: {}",
+        report.original_digest,
+        report.filtered_digest,
+        name = report.hash.name().to_uppercase()
+    )?;
+
+    if let Some(encoding) = result.encoding {
+        writeln!(out, "Detected encoding: {encoding}")?;
+    }
+
+    if let Some((form, before, after)) = result.normalized {
+        match before == after {
+            true => writeln!(out, "Normalized to {}: byte count unchanged", form.name())?,
+            false => writeln!(
+                out,
+                "Normalized to {}: {before} -> {after} bytes",
+                form.name()
+            )?,
+        }
+    }
+
+    if result.changed_lines > 0 {
+        writeln!(
+            out,
+            "Changed lines: {} ({} bytes)",
+            result.changed_lines, result.changed_bytes
+        )?;
+    }
+    if !report.post.is_empty() {
+        let passes: Vec<String> = report
+            .post
+            .iter()
+            .map(|(pass, changed)| match changed {
+                true => format!("{} changed the content", pass.name()),
+                false => format!("{} changed nothing", pass.name()),
+            })
+            .collect();
+        writeln!(out, "Post-processing: {}", passes.join(", "))?;
+    }
+
+    if result.converted_line_endings > 0 {
+        writeln!(
+            out,
+            "Converted {} line endings",
+            result.converted_line_endings
+        )?;
+    }
+
+    if let Some(bom) = result.bom {
+        writeln!(out, "UTF-8 byte order mark {}", bom_action(bom))?;
+    }
+
+    if result.normalized_spaces > 0 {
+        writeln!(
+            out,
+            "Normalized {} whitespace characters to spaces",
+            result.normalized_spaces
+        )?;
+    }
+
+    if result.transliterated > 0 {
+        writeln!(
+            out,
+            "Transliterated {} of them to ASCII",
+            result.transliterated
+        )?;
+    }
+
+    if !result.untransliterated.is_empty() {
+        let chars: Vec<_> = result
+            .untransliterated
+            .iter()
+            .map(|(ch, n)| format!("{ch} \u{d7}{n}"))
+            .collect();
+        writeln!(out, "No transliteration for: {}", chars.join(", "))?;
+    }
+
+    if !result.long_lines.is_empty() {
+        let lines: Vec<_> = result.long_lines.iter().map(|n| n.to_string()).collect();
+        let message = format!("Long lines left untouched: {}", lines.join(", "));
+        writeln!(out, "{}", palette.warn(&message))?;
+    }
+
+    if !result.emptied_lines.is_empty() {
+        let lines: Vec<_> = result.emptied_lines.iter().map(|n| n.to_string()).collect();
+        writeln!(out, "Lines emptied by the filter: {}", lines.join(", "))?;
+    }
+
+    if !result.char_counts.is_empty() {
+        let counts: Vec<String> = result
+            .frequent_chars()
+            .iter()
+            .map(|(ch, n)| match invisible_name(*ch) {
+                Some(_) => format!("U+{:04X} \u{d7}{n}", *ch as u32),
+                None => format!("{ch} \u{d7}{n}"),
+            })
+            .collect();
+        writeln!(out, "\nCharacters: {}", counts.join(", "))?;
+    }
+
+    if !result.clusters.is_empty() {
+        writeln!(out, "\nNon-ASCII clusters:")?;
+        for cluster in limited(&result.clusters, report.limit) {
+            let points = code_points(&cluster.text);
+            let entry = format!(
+                "  line {}, col {}, byte {}: '{}' ({})",
+                cluster.line,
+                cluster.column,
+                cluster.offset,
+                cluster.text,
+                points.join(" ")
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.clusters.len(), report.limit)?;
+    } else if !result.non_ascii_positions.is_empty() && !report.per_char {
+        let runs = result.non_ascii_runs();
+        writeln!(out, "\nNon-ASCII runs:")?;
+        for run in limited(&runs, report.limit) {
+            let text = String::from_utf8_lossy(&run.bytes);
+            let entry = match run.chars {
+                1 => format!(
+                    "  line {}, col {}, byte {}: '{text}' ({})",
+                    run.line,
+                    run.column,
+                    run.offset,
+                    code_points(&text).join(" ")
+                ),
+                n => format!(
+                    "  line {}, cols {}-{}, byte {}: '{text}' ({n} chars)",
+                    run.line,
+                    run.column,
+                    run.column + n - 1,
+                    run.offset
+                ),
+            };
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, runs.len(), report.limit)?;
+    } else if !result.non_ascii_positions.is_empty() {
+        writeln!(out, "\nNon-ASCII positions:")?;
+        for p in limited(&result.non_ascii_positions, report.limit) {
+            let entry = format!(
+                "  line {}, col {}, byte {}: '{}' (U+{:04X})",
+                p.line, p.column, p.offset, p.ch, p.ch as u32
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.non_ascii_positions.len(), report.limit)?;
+    }
+
+    if !result.invisible_positions.is_empty() {
+        writeln!(
+            out,
+            "\nInvisible characters ({}):",
+            result.invisible_positions.len()
+        )?;
+        for p in limited(&result.invisible_positions, report.limit) {
+            let entry = format!(
+                "  line {}, col {}, byte {}: U+{:04X} {}",
+                p.line,
+                p.column,
+                p.offset,
+                p.ch as u32,
+                invisible_name(p.ch).unwrap_or_default()
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.invisible_positions.len(), report.limit)?;
+        if result
+            .invisible_positions
+            .iter()
+            .any(|p| is_bidi_control(p.ch))
+        {
+            writeln!(
+                out,
+                "{}",
+                palette.warn("  warning: bidirectional control characters can reorder how code is displayed (Trojan Source)")
+            )?;
+        }
+    }
+
+    if !result.control_positions.is_empty() {
+        writeln!(
+            out,
+            "\nControl characters ({}):",
+            result.control_positions.len()
+        )?;
+        for p in limited(&result.control_positions, report.limit) {
+            let entry = format!(
+                "  line {}, col {}, byte {}: U+{:04X} {}",
+                p.line,
+                p.column,
+                p.offset,
+                p.ch as u32,
+                control_name(p.ch).unwrap_or_default()
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.control_positions.len(), report.limit)?;
+    }
+
+    if !result.denied_positions.is_empty() {
+        writeln!(
+            out,
+            "\nDenied characters ({}):",
+            result.denied_positions.len()
+        )?;
+        for p in limited(&result.denied_positions, report.limit) {
+            let entry = format!(
+                "  line {}, col {}, byte {}: '{}' (U+{:04X})",
+                p.line, p.column, p.offset, p.ch, p.ch as u32
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.denied_positions.len(), report.limit)?;
+    }
+
+    if !result.mixed_scripts.is_empty() {
+        writeln!(
+            out,
+            "\nMixed-script words ({}):",
+            result.mixed_scripts.len()
+        )?;
+        for mixed in &result.mixed_scripts {
+            let scripts: Vec<_> = mixed.scripts.iter().map(|script| script.name()).collect();
+            let entry = format!(
+                "  line {}, col {}: '{}' mixes {}",
+                mixed.line,
+                mixed.column,
+                mixed.word,
+                scripts.join(" and ")
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        writeln!(
+            out,
+            "{}",
+            palette.warn("  warning: look-alike letters from different scripts are a common spoofing trick (homoglyphs)")
+        )?;
+    }
+
+    if !result.zero_width_runs.is_empty() {
+        writeln!(
+            out,
+            "\nZero-width watermarks ({}):",
+            result.zero_width_runs.len()
+        )?;
+        for run in limited(&result.zero_width_runs, report.limit) {
+            let points = code_points(&run.text);
+            let entry = format!(
+                "  line {}, col {}, byte {}: {} zero-width characters ({})",
+                run.line,
+                run.column,
+                run.offset,
+                points.len(),
+                points.join(" ")
+            );
+            writeln!(out, "{}", palette.bad(&entry))?;
+        }
+        print_more(out, result.zero_width_runs.len(), report.limit)?;
+    }
+
+    if let Some(limit) = report.hex
+        && report.shows_entropy()
+    {
+        let runs: Vec<_> = result
+            .runs()
+            .into_iter()
+            .filter(|run| run.bytes.len() >= HEX_MIN_RUN)
+            .collect();
+        if !runs.is_empty() {
+            writeln!(out, "\nRemoved byte runs:")?;
+        }
+        for run in runs {
+            let shown = &run.bytes[..run.bytes.len().min(limit)];
+            let hex: Vec<String> = shown.iter().map(|b| format!("{b:02x}")).collect();
+            let more = if shown.len() < run.bytes.len() {
+                " ..."
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  line {}, col {}, byte {} ({} bytes): {}{more}",
+                run.line,
+                run.column,
+                run.offset,
+                run.bytes.len(),
+                hex.join(" ")
+            )?;
+        }
+    }
+
+    // A repeated banner is listed once with its count
+    if !result.skipped_lines.is_empty() {
+        writeln!(out, "\nRemoved watermarks:")?;
+        for block in result.watermark_blocks() {
+            for skipped in block.lines {
+                let entry = format!(
+                    "  line {}, col {}: {} (matched '{}', {})",
+                    skipped.line,
+                    skipped.column,
+                    skipped.text.trim(),
+                    skipped.mark,
+                    skipped.pattern
+                );
+                writeln!(out, "{}", palette.warn(&entry))?;
+            }
+            if let [.., last] = block.starts[..]
+                && block.starts.len() > 1
+            {
+                let banner = match block.lines.len() {
+                    1 => "watermark line".to_string(),
+                    n => format!("watermark banner of {n} lines"),
+                };
+                let entry = format!(
+                    "  {banner} repeated {}\u{d7}, the last time at line {last}",
+                    block.starts.len()
+                );
+                writeln!(out, "{}", palette.warn(&entry))?;
+            }
+        }
+    }
+
+    let by_pattern = result.watermarks_by_pattern();
+    if !by_pattern.is_empty() {
+        writeln!(out, "\nWatermarks by pattern:")?;
+        for (pattern, count) in by_pattern {
+            writeln!(out, "  {pattern}: {count}")?;
+        }
+    }
+
+    print_kept_watermarks(out, result, palette)?;
+    print_line_fates(out, result, palette)
+}
+
+// Every line's disposition for --verbose
+fn print_line_fates(out: &mut dyn Write, result: &Report, palette: Palette) -> io::Result<()> {
+    if result.line_fates.is_empty() {
+        return Ok(());
+    }
+
+    let width = result.line_fates.len().to_string().len();
+    writeln!(out, "\nLines:")?;
+    for (n, fate) in result.line_fates.iter().enumerate() {
+        let line = n + 1;
+        match fate {
+            LineFate::Kept => writeln!(out, "  {line:>width$} kept")?,
+            LineFate::Watermark => {
+                let entry = format!("  {line:>width$} watermark, removed");
+                writeln!(out, "{}", palette.warn(&entry))?
+            }
+            LineFate::WatermarkKept => {
+                let entry = format!("  {line:>width$} watermark, kept");
+                writeln!(out, "{}", palette.warn(&entry))?
+            }
+            LineFate::Stripped(chars) => {
+                let entry = format!("  {line:>width$} stripped {chars} characters");
+                writeln!(out, "{}", palette.bad(&entry))?
+            }
+            LineFate::TooLong => {
+                let entry = format!("  {line:>width$} too long, left untouched");
+                writeln!(out, "{}", palette.warn(&entry))?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The audit log of --no-strip-watermarks and --keep-line
+fn print_kept_watermarks(out: &mut dyn Write, result: &Report, palette: Palette) -> io::Result<()> {
+    let sections = [
+        ("Watermarks kept", &result.kept_watermarks),
+        (
+            "Watermarks exempted by --keep-line",
+            &result.exempted_watermarks,
+        ),
+    ];
+    for (title, lines) in sections {
+        if lines.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n{title} ({}):", lines.len())?;
+        for kept in lines {
+            let entry = format!(
+                "  line {}, col {}: {} (matched '{}', {})",
+                kept.line,
+                kept.column,
+                kept.text.trim(),
+                kept.mark,
+                kept.pattern
+            );
+            writeln!(out, "{}", palette.warn(&entry))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Compact totals for --stats-only
+pub fn print_stats(out: &mut dyn Write, report: &FileReport, with_name: bool) -> io::Result<()> {
+    let result = report.result;
+    if with_name {
+        write!(out, "{}: ", report.name)?;
+    }
+
+    writeln!(
+        out,
+        "skipped={} nonascii={} invisible={} entropy={:.2}",
+        result.skipped_lines.len(),
+        result.non_ascii_positions.len(),
+        result.invisible_positions.len(),
+        report.entropy
+    )
+}
+
+// One `path\tline\tcolumn\tchar\0` record per removed character in file
+// order. Controls, a tab among them, are spelled `U+XXXX` to keep fields apart
+pub fn print_nul(out: &mut dyn Write, report: &FileReport) -> io::Result<()> {
+    let result = report.result;
+    let mut found: Vec<&Position> = result
+        .non_ascii_positions
+        .iter()
+        .chain(&result.invisible_positions)
+        .chain(&result.control_positions)
+        .chain(&result.denied_positions)
+        .collect();
+    found.sort_by_key(|p| p.offset);
+    for p in found {
+        write!(out, "{}\t{}\t{}\t", report.name, p.line, p.column)?;
+        match p.ch.is_control() {
+            true => write!(out, "U+{:04X}\0", p.ch as u32)?,
+            false => write!(out, "{}\0", p.ch)?,
+        }
+    }
+    Ok(())
+}
+
+// `text` without the ANSI colour sequences of a `Palette`, for files
+pub fn strip_colors(text: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.iter().position(|&b| b == 0x1b) {
+        plain.extend_from_slice(&rest[..at]);
+        rest = &rest[at..];
+        // Everything up to and including the final letter of `ESC [ ... m`
+        let end = match rest.get(1) {
+            Some(b'[') => rest[2..]
+                .iter()
+                .position(u8::is_ascii_alphabetic)
+                .map_or(rest.len(), |end| end + 3),
+            _ => 1,
+        };
+        rest = &rest[end..];
+    }
+    plain.extend_from_slice(rest);
+    plain
+}
+
+pub fn print_diff_stat(out: &mut dyn Write, report: &FileReport) -> io::Result<()> {
+    let result = report.result;
+    if !report.changed {
+        return writeln!(
+            out,
+            "{}: {}",
+            report.name,
+            report.palette.good("no changes")
+        );
+    }
+    writeln!(
+        out,
+        "{}: {} lines, {} bytes {}",
+        report.name,
+        result.changed_lines,
+        result.changed_bytes,
+        if report.written {
+            "changed"
+        } else {
+            "would change"
+        }
+    )
+}
+
+// One line per file for --check
+pub fn print_check(out: &mut dyn Write, report: &FileReport) -> io::Result<()> {
+    let result = report.result;
+    if !report.dirty {
+        return writeln!(out, "{}: {}", report.name, report.palette.good("clean"));
+    }
+    if !report.fails_check() {
+        let within = match report.entropy_threshold {
+            Some(_) => format!("entropy {:.2} bits/byte within threshold", report.entropy),
+            None => format!("{:.2}% within limit", result.non_ascii_percent()),
+        };
+        return writeln!(
+            out,
+            "{}: ok ({} non-ASCII characters, {within})",
+            report.name,
+            result.non_ascii_positions.len(),
+        );
+    }
+
+    writeln!(
+        out,
+        "{}: {} ({} non-ASCII characters, {} invisible characters, {} watermark lines)",
+        report.name,
+        report.palette.bad(if report.too_much_non_ascii() {
+            "dirty, mostly non-ASCII"
+        } else if report.high_entropy() {
+            "dirty, high entropy"
+        } else {
+            "dirty"
+        }),
+        result.non_ascii_positions.len(),
+        result.invisible_positions.len(),
+        result.skipped_lines.len()
+    )
+}
+
+// The lines around each watermark, for --context, to spot false positives
+pub fn print_watermark_context(
+    out: &mut dyn Write,
+    original: &[u8],
+    result: &Report,
+    lines: usize,
+    palette: Palette,
+) -> io::Result<()> {
+    let mut marked: Vec<usize> = result
+        .skipped_lines
+        .iter()
+        .chain(&result.kept_watermarks)
+        .map(|found| found.line)
+        .collect();
+    if marked.is_empty() {
+        return Ok(());
+    }
+    marked.sort();
+
+    let text = String::from_utf8_lossy(original);
+    let source: Vec<&str> = text.lines().collect();
+    let width = source.len().to_string().len();
+
+    writeln!(out, "\nWatermark context:")?;
+    for line in marked {
+        let first = line.saturating_sub(lines).max(1);
+        let last = (line + lines).min(source.len());
+        writeln!(out, "  --- line {line} ---")?;
+        for n in first..=last {
+            let text = source[n - 1];
+            match n == line {
+                true => writeln!(out, "{}", palette.warn(&format!("> {n:>width$} | {text}")))?,
+                false => writeln!(out, "  {n:>width$} | {text}")?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Line based unified diff between the input and the cleaned output
+pub fn print_diff(
+    out: &mut dyn Write,
+    name: &str,
+    original: &[u8],
+    filtered: &[u8],
+    palette: Palette,
+) -> io::Result<()> {
+    let original = String::from_utf8_lossy(original);
+    let filtered = String::from_utf8_lossy(filtered);
+    let diff = TextDiff::from_lines(original.as_ref(), filtered.as_ref());
+    let unified = diff
+        .unified_diff()
+        .header(name, &format!("{name} (filtered)"))
+        .to_string();
+
+    for line in unified.lines() {
+        let line = match line.as_bytes().first() {
+            Some(b'-') => palette.bad(line),
+            Some(b'+') => palette.good(line),
+            _ => line.to_string(),
+        };
+        writeln!(out, "{line}")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use remove_water::{Position, Report, invisible_name, is_bidi_control};
+use serde::Serialize;
+use std::io::{self, Write};
+
+// Rule ids, one per kind of finding
+const RULES: &[(&str, &str)] = &[
+    ("non-ascii", "Non-ASCII character"),
+    (
+        "invisible-char",
+        "Zero-width or bidirectional control character",
+    ),
+    ("control-char", "ASCII control character"),
+    ("denied-char", "Character on the deny list"),
+    ("mixed-script", "Word mixing look-alike scripts"),
+    ("watermark", "Watermark line"),
+    ("zero-width-watermark", "Run of zero-width characters"),
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    rule_id: &'static str,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+}
+
+fn result(
+    path: &str,
+    rule_id: &'static str,
+    level: &'static str,
+    (line, column): (usize, usize),
+    text: String,
+) -> SarifResult {
+    SarifResult {
+        rule_id,
+        level,
+        message: Message { text },
+        locations: vec![Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri: uri(path) },
+                region: Region {
+                    start_line: line,
+                    start_column: column,
+                },
+            },
+        }],
+    }
+}
+
+// `path` as a relative URI reference: `/` separated, with every byte outside
+// the unreserved set percent-encoded
+fn uri(path: &str) -> String {
+    let mut uri = String::with_capacity(path.len());
+    for byte in path.replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+fn code_point(p: &Position) -> String {
+    format!("U+{:04X}", p.ch as u32)
+}
+
+// SARIF results for everything `report` found in `path`
+pub fn results(path: &str, report: &Report) -> Vec<SarifResult> {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let mut found = Vec::new();
+
+    for p in &report.non_ascii_positions {
+        let text = format!("Non-ASCII character '{}' ({})", p.ch, code_point(p));
+        found.push(result(
+            path,
+            "non-ascii",
+            "warning",
+            (p.line, p.column),
+            text,
+        ));
+    }
+    for p in &report.invisible_positions {
+        let text = format!(
+            "Invisible character {} {}",
+            code_point(p),
+            invisible_name(p.ch).unwrap_or_default()
+        );
+        let level = if is_bidi_control(p.ch) {
+            "error"
+        } else {
+            "warning"
+        };
+        found.push(result(
+            path,
+            "invisible-char",
+            level,
+            (p.line, p.column),
+            text,
+        ));
+    }
+    for p in &report.control_positions {
+        let text = format!("Control character {}", code_point(p));
+        found.push(result(
+            path,
+            "control-char",
+            "warning",
+            (p.line, p.column),
+            text,
+        ));
+    }
+    for p in &report.denied_positions {
+        let text = format!("Denied character '{}' ({})", p.ch, code_point(p));
+        found.push(result(
+            path,
+            "denied-char",
+            "warning",
+            (p.line, p.column),
+            text,
+        ));
+    }
+    for mixed in &report.mixed_scripts {
+        let scripts: Vec<_> = mixed.scripts.iter().map(|script| script.name()).collect();
+        let text = format!("'{}' mixes {}", mixed.word, scripts.join(" and "));
+        let at = (mixed.line, mixed.column);
+        found.push(result(path, "mixed-script", "error", at, text));
+    }
+    for skipped in report.skipped_lines.iter().chain(&report.kept_watermarks) {
+        let text = format!(
+            "Watermark line matching '{}' ({})",
+            skipped.mark, skipped.pattern
+        );
+        let at = (skipped.line, skipped.column);
+        found.push(result(path, "watermark", "note", at, text));
+    }
+    for run in &report.zero_width_runs {
+        let text = format!(
+            "Possible invisible watermark: {} zero-width characters in a row",
+            run.text.chars().count()
+        );
+        let at = (run.line, run.column);
+        found.push(result(path, "zero-width-watermark", "warning", at, text));
+    }
+
+    found
+}
+
+// One SARIF 2.1.0 log with a single run holding every result
+pub fn print(out: &mut dyn Write, results: &[SarifResult]) -> io::Result<()> {
+    let rules: Vec<_> = RULES
+        .iter()
+        .map(|(id, text)| {
+            serde_json::json!({
+                "id": id,
+                "shortDescription": { "text": text },
+            })
+        })
+        .collect();
+    let log = serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "remove_water",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+
+    serde_json::to_writer_pretty(&mut *out, &log)?;
+    writeln!(out)
+}
@@ -0,0 +1,79 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{Options, Position, Report, default_action, normalize, scan_line};
+use std::io::{self, BufRead, Write};
+
+// Streaming counterpart of `scan_with_options` for inputs too large to load:
+// reads `reader` line by line and writes the filtered lines to `writer`. The
+// report matches the in-memory scan for UTF-8 input; `detect_encoding` needs
+// the whole input and is ignored, invalid bytes are replaced per line.
+pub fn scan_reader<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    opts: &Options,
+) -> io::Result<Report> {
+    let mut report = Report::default();
+    let mut raw = Vec::new();
+    let mut filtered = Vec::new();
+    let mut line_no = 0;
+    let mut offset = 0;
+    let mut sizes = (0, 0);
+
+    loop {
+        raw.clear();
+        if reader.read_until(b'\n', &mut raw)? == 0 {
+            break;
+        }
+        line_no += 1;
+        report.input_bytes += raw.len();
+
+        let decoded = String::from_utf8_lossy(&raw);
+        let text = match opts.normalize {
+            Some(form) => {
+                let normalized = normalize(&decoded, form);
+                sizes.0 += decoded.len();
+                sizes.1 += normalized.len();
+                normalized.into()
+            }
+            None => decoded,
+        };
+
+        filtered.clear();
+        scan_line(
+            &text,
+            line_no,
+            offset,
+            opts,
+            &mut |position: &Position| default_action(position.ch, opts),
+            &mut report,
+            &mut filtered,
+        );
+        writer.write_all(&filtered)?;
+        offset += text.len();
+    }
+
+    report.normalized = opts.normalize.map(|form| (form, sizes.0, sizes.1));
+    writer.flush()?;
+    Ok(report)
+}
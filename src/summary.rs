@@ -0,0 +1,104 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use entropy::shannon_entropy;
+use serde::Serialize;
+use std::io::{self, Write};
+
+// One file's line in the --summary-json roll-up
+#[derive(Serialize)]
+pub struct FileSummary {
+    path: String,
+    changed: bool,
+    written: bool,
+    binary: bool,
+    // Skipped by policy: an --exclude glob matched it
+    exempt: bool,
+    skipped_lines: usize,
+    non_ascii_bytes: usize,
+}
+
+impl FileSummary {
+    pub fn new(path: &str, changed: bool, written: bool, binary: bool) -> FileSummary {
+        FileSummary {
+            path: path.to_string(),
+            changed,
+            written,
+            binary,
+            exempt: false,
+            skipped_lines: 0,
+            non_ascii_bytes: 0,
+        }
+    }
+
+    pub fn exempt(mut self, exempt: bool) -> FileSummary {
+        self.exempt = exempt;
+        self
+    }
+
+    pub fn counts(mut self, skipped_lines: usize, non_ascii_bytes: usize) -> FileSummary {
+        self.skipped_lines = skipped_lines;
+        self.non_ascii_bytes = non_ascii_bytes;
+        self
+    }
+}
+
+// Field names are part of the tool's interface, keep them stable
+#[derive(Serialize)]
+struct Summary<'a> {
+    files: usize,
+    changed: usize,
+    written: usize,
+    binary_skipped: usize,
+    exempt: usize,
+    errors: usize,
+    skipped_lines: usize,
+    non_ascii_bytes: usize,
+    // Of every removed byte of the run taken together
+    entropy: f32,
+    per_file: &'a [FileSummary],
+}
+
+// Prints the whole run as one JSON object; `removed` holds the removed bytes of
+// all files concatenated
+pub fn print(
+    out: &mut dyn Write,
+    files: &[FileSummary],
+    removed: &[u8],
+    errors: usize,
+) -> io::Result<()> {
+    let summary = Summary {
+        files: files.len(),
+        changed: files.iter().filter(|file| file.changed).count(),
+        written: files.iter().filter(|file| file.written).count(),
+        binary_skipped: files.iter().filter(|file| file.binary).count(),
+        exempt: files.iter().filter(|file| file.exempt).count(),
+        errors,
+        skipped_lines: files.iter().map(|file| file.skipped_lines).sum(),
+        non_ascii_bytes: files.iter().map(|file| file.non_ascii_bytes).sum(),
+        entropy: shannon_entropy(removed),
+        per_file: files,
+    };
+    serde_json::to_writer(&mut *out, &summary)?;
+    writeln!(out)
+}
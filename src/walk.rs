@@ -0,0 +1,217 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::backup::is_backup;
+use crate::cli::Input;
+use glob::{MatchOptions, Pattern, glob_with};
+use ignore::{Error, WalkBuilder};
+use log::debug;
+use std::collections::HashSet;
+use std::fs::canonicalize;
+use std::path::Path;
+
+// Which files a directory argument expands to
+pub struct WalkOptions {
+    // Lowercase extensions without the leading dot; empty accepts every file
+    pub extensions: Vec<String>,
+    // Directory levels to descend, 1 meaning only the directory's own files
+    pub max_depth: Option<usize>,
+    // Leave out whatever .gitignore, .ignore and .git/info/exclude exclude
+    pub respect_ignore: bool,
+    // Descend into linked directories and clean linked files, never twice
+    pub follow_links: bool,
+    // Files left alone whatever their content, see `excluded_by`
+    pub exclude: Vec<Pattern>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            extensions: Vec::new(),
+            max_depth: None,
+            respect_ignore: true,
+            follow_links: false,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+fn wanted(path: &Path, opts: &WalkOptions) -> bool {
+    // Cleaning a backup would lose the original it was made to keep
+    if is_backup(path) {
+        return false;
+    }
+
+    if opts.extensions.is_empty() {
+        return true;
+    }
+
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            opts.extensions
+                .iter()
+                .any(|want| want.eq_ignore_ascii_case(ext))
+        })
+}
+
+// The --exclude glob `path` matches, tried against the path itself, its file
+// name and every directory it is in, so `*.md` and `tests/data` both work
+pub fn excluded_by<'a>(path: &str, opts: &'a WalkOptions) -> Option<&'a Pattern> {
+    let path = Path::new(path.strip_prefix("./").unwrap_or(path));
+    let names: Vec<&Path> = path
+        .ancestors()
+        .filter(|part| !part.as_os_str().is_empty())
+        .chain(path.file_name().map(Path::new))
+        .collect();
+    opts.exclude
+        .iter()
+        .find(|pattern| names.iter().any(|name| pattern.matches_path(name)))
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+fn is_loop(err: &Error) -> bool {
+    match err {
+        Error::Loop { .. } => true,
+        Error::WithPath { err, .. } | Error::WithDepth { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+fn walk_dir(
+    dir: &str,
+    opts: &WalkOptions,
+    files: &mut Vec<Input>,
+    errors: &mut Vec<(String, String)>,
+) {
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .git_ignore(opts.respect_ignore)
+        .git_exclude(opts.respect_ignore)
+        .git_global(opts.respect_ignore)
+        .ignore(opts.respect_ignore)
+        .parents(opts.respect_ignore)
+        .max_depth(opts.max_depth)
+        // Links stay untouched by default, and followed ones are checked for loops
+        .follow_links(opts.follow_links)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        // Hidden directories such as .git are never entered, hidden files are fine
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_some_and(|t| t.is_dir())
+                || !is_hidden(entry.path())
+        })
+        .build();
+
+    for entry in walker {
+        match entry {
+            Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                if wanted(entry.path(), opts) {
+                    files.push(Input::File(entry.path().to_string_lossy().into_owned()));
+                }
+            }
+            Ok(_) => {}
+            // A link back up the tree has already been walked through once
+            Err(err) if is_loop(&err) => debug!("{dir}: not following {err}"),
+            Err(err) => errors.push((dir.to_string(), err.to_string())),
+        }
+    }
+}
+
+// Whether `path` is meant as a glob rather than the name of an existing file
+pub fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '[']) && !Path::new(path).exists()
+}
+
+// The paths a glob argument matches, sorted, with shell rules for hidden files
+fn expand_glob(pattern: &str, matched: &mut Vec<String>, errors: &mut Vec<(String, String)>) {
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+    let paths = match glob_with(pattern, options) {
+        Ok(paths) => paths,
+        Err(err) => return errors.push((pattern.to_string(), format!("invalid glob: {err}"))),
+    };
+
+    let before = matched.len();
+    for path in paths {
+        match path {
+            Ok(path) => matched.push(path.to_string_lossy().into_owned()),
+            Err(err) => errors.push((pattern.to_string(), err.to_string())),
+        }
+    }
+    if matched.len() == before {
+        errors.push((
+            pattern.to_string(),
+            "no files match this pattern".to_string(),
+        ));
+    }
+}
+
+// Replaces every glob in `inputs` with the paths it matches, the shells on
+// Windows leave that to the program, and every directory with the matching
+// files below it
+pub fn expand(inputs: Vec<Input>, opts: &WalkOptions) -> (Vec<Input>, Vec<(String, String)>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for input in inputs {
+        let paths = match input {
+            // An existing file wins over a name that merely looks like a glob
+            Input::File(path) if is_pattern(&path) => {
+                let mut matched = Vec::new();
+                expand_glob(&path, &mut matched, &mut errors);
+                matched
+            }
+            Input::File(path) => vec![path],
+            Input::Stdin => {
+                files.push(Input::Stdin);
+                continue;
+            }
+        };
+        for path in paths {
+            if Path::new(&path).is_dir() {
+                walk_dir(&path, opts, &mut files, &mut errors);
+            } else {
+                files.push(Input::File(path));
+            }
+        }
+    }
+
+    // Two links to one file would otherwise clean it twice
+    if opts.follow_links {
+        let mut seen = HashSet::new();
+        files.retain(|input| match input {
+            Input::File(path) => seen.insert(canonicalize(path).unwrap_or_else(|_| path.into())),
+            Input::Stdin => true,
+        });
+    }
+
+    (files, errors)
+}
@@ -0,0 +1,129 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+
+// Built-in watermark regexes under stable names, which --disable-pattern and
+// the report refer to
+pub static WATERMARK_PATTERNS: &[(&str, &str)] = &[
+    ("doc-comment", r"///"),
+    ("inner-doc-comment", r"//!"),
+    ("block-doc-comment", r"/\*\*"),
+    ("inner-block-doc-comment", r"/\*!"),
+    ("generated-by", r"// Generated by"),
+    ("code-generated-by", r"// Code generated by"),
+    ("auto-generated", r"// Auto-generated"),
+    ("ai-generated", r"// AI generated"),
+    ("llm", r"// LLM"),
+    ("at-generated", r"// @generated"),
+    ("spdx", r"// SPDX-License-Identifier:"),
+];
+
+// A compiled watermark regex and the name it is reported under
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub name: String,
+    pub regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(name: &str, source: &str) -> Result<Pattern, regex::Error> {
+        Ok(Pattern {
+            name: name.to_string(),
+            regex: compile_pattern(source)?,
+        })
+    }
+}
+
+// Every watermark regex, built-in or user supplied, is compiled here
+pub fn compile_pattern(source: &str) -> Result<Regex, regex::Error> {
+    Regex::new(source)
+}
+
+pub fn compile_patterns(sources: &[(&str, &str)]) -> Result<Vec<Pattern>, regex::Error> {
+    sources
+        .iter()
+        .map(|(name, source)| Pattern::new(name, source))
+        .collect()
+}
+
+pub fn builtin_patterns() -> Result<Vec<Pattern>, regex::Error> {
+    compile_patterns(WATERMARK_PATTERNS)
+}
+
+// The built-in pattern called `name`, or at 1-based position `name`
+pub fn find_builtin(name: &str) -> Option<&'static str> {
+    match name.parse::<usize>() {
+        Ok(index) => WATERMARK_PATTERNS.get(index.checked_sub(1)?),
+        Err(_) => WATERMARK_PATTERNS
+            .iter()
+            .find(|(builtin, _)| *builtin == name),
+    }
+    .map(|(builtin, _)| *builtin)
+}
+
+// A pattern file entry that failed to compile
+#[derive(Debug)]
+pub struct PatternError {
+    pub line: usize,
+    pub source: regex::Error,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid watermark pattern on line {}: {}",
+            self.line, self.source
+        )
+    }
+}
+
+impl Error for PatternError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+// Compiles one regex per non-empty line of a pattern file, each named after
+// `source` and its line, e.g. `extra.txt:3`
+pub fn parse_patterns(source: &str, text: &str) -> Result<Vec<Pattern>, PatternError> {
+    let mut patterns = Vec::new();
+
+    for (line_no, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let name = format!("{source}:{}", line_no + 1);
+        let pattern = Pattern::new(&name, line).map_err(|source| PatternError {
+            line: line_no + 1,
+            source,
+        })?;
+        patterns.push(pattern);
+    }
+
+    Ok(patterns)
+}
@@ -0,0 +1,41 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+// The library entry points as a dependency would call them
+
+use remove_water::{Options, filter_bytes};
+
+#[test]
+fn filter_bytes_returns_the_cleaned_bytes_and_the_report() {
+    let opts = Options {
+        replacement: Some("?".to_string()),
+        allow: ['\u{a9}'].into_iter().collect(),
+        whole_line_watermarks: true,
+        ..Options::default()
+    };
+    let (cleaned, report) = filter_bytes("\u{a9} na\u{ef}ve\n/// doc\n///\n".as_bytes(), &opts);
+    assert_eq!(cleaned, "\u{a9} na?ve\n/// doc\n\n".as_bytes());
+    let position = report.non_ascii_positions[0];
+    assert_eq!((position.line, position.column, position.offset), (1, 5, 5));
+    assert_eq!(report.skipped_lines.len(), 1);
+}
@@ -0,0 +1,1512 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+// End-to-end checks of the binary on files in a scratch directory
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// A fresh directory under the system temp dir, removed again on drop
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("remove_water-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.0.join(name)).unwrap()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_remove_water"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn run_stdin(dir: &Path, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_remove_water"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A usage error exits before the input is read, closing the pipe early
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn cleans_non_ascii_and_watermarks_in_place() {
+    let scratch = Scratch::new("clean");
+    scratch.write("a.txt", "caf\u{e9}\n/// doc\nok\n");
+
+    let cleaned = run(&scratch.0, &["--write", "a.txt"]);
+    assert_eq!(cleaned.status.code(), Some(2));
+    assert!(
+        stdout(&cleaned).contains("1 watermarks removed"),
+        "{}",
+        stdout(&cleaned)
+    );
+    assert_eq!(scratch.read("a.txt"), "caf\n\nok\n");
+
+    let again = run(&scratch.0, &["a.txt"]);
+    assert!(
+        stdout(&again).contains("File is clean"),
+        "{}",
+        stdout(&again)
+    );
+}
+
+#[test]
+fn dry_run_reports_without_writing() {
+    let scratch = Scratch::new("dry-run");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    assert_eq!(
+        run(&scratch.0, &["--dry-run", "a.txt"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(scratch.read("a.txt"), "caf\u{e9}\n");
+    scratch.write("b.txt", "plain\n");
+    assert_eq!(
+        run(&scratch.0, &["--dry-run", "b.txt"]).status.code(),
+        Some(0)
+    );
+}
+
+#[test]
+fn stdin_is_cleaned_to_stdout_with_the_report_on_stderr() {
+    let scratch = Scratch::new("stdin");
+
+    let piped = run_stdin(&scratch.0, &["-"], "caf\u{e9}\n");
+    assert_eq!(stdout(&piped), "caf\n");
+    assert!(
+        stderr(&piped).contains("Non-ASCII runs"),
+        "{}",
+        stderr(&piped)
+    );
+}
+
+#[test]
+fn positions_count_characters_not_bytes() {
+    let scratch = Scratch::new("per-char");
+
+    let piped = run_stdin(&scratch.0, &["--per-char", "-"], "x\u{e9}\u{1f642}y\n");
+    let report = stderr(&piped);
+    assert!(
+        report.contains("Filtered 2 non-ASCII characters"),
+        "{report}"
+    );
+    assert!(
+        report.contains("line 1, col 3, byte 3: '\u{1f642}' (U+1F642)"),
+        "{report}"
+    );
+    assert_eq!(stdout(&piped), "xy\n");
+}
+
+#[test]
+fn replace_substitutes_instead_of_deleting() {
+    let scratch = Scratch::new("replace");
+
+    let default = run_stdin(&scratch.0, &["--replace", "-"], "na\u{ef}ve\n");
+    assert_eq!(stdout(&default), "na?ve\n");
+    let custom = run_stdin(&scratch.0, &["--replace=_", "-"], "na\u{ef}ve\n");
+    assert_eq!(stdout(&custom), "na_ve\n");
+}
+
+#[test]
+fn translit_spells_accents_and_punctuation_in_ascii() {
+    let scratch = Scratch::new("translit");
+
+    let input = "caf\u{e9} \u{2014} \u{65e5}\n";
+    assert_eq!(
+        stdout(&run_stdin(&scratch.0, &["--translit", "-"], input)),
+        "cafe -- \n"
+    );
+    let replaced = run_stdin(&scratch.0, &["--translit", "--replace", "-"], input);
+    assert_eq!(stdout(&replaced), "cafe -- ?\n");
+}
+
+#[test]
+fn patterns_file_adds_or_replaces_watermarks() {
+    let scratch = Scratch::new("patterns");
+    scratch.write("patterns.txt", "TODO\\(ai\\)\n");
+    let input = "a\n# TODO(ai) x\n/// doc\n";
+
+    let added = run_stdin(&scratch.0, &["--patterns", "patterns.txt", "-"], input);
+    assert_eq!(stdout(&added), "a\n\n\n");
+    let only = ["--patterns", "patterns.txt", "--no-builtin-patterns", "-"];
+    assert_eq!(
+        stdout(&run_stdin(&scratch.0, &only, input)),
+        "a\n\n/// doc\n"
+    );
+
+    scratch.write("bad.txt", "ok\n(\n");
+    let bad = run_stdin(&scratch.0, &["--patterns", "bad.txt", "-"], input);
+    assert!(!bad.status.success());
+    assert!(stderr(&bad).contains("line 2"), "{}", stderr(&bad));
+}
+
+#[test]
+fn builtin_patterns_match_literally() {
+    let scratch = Scratch::new("builtin_patterns");
+    let input = "/** doc */\n/* plain */\n//! crate\n// @generated\nx\n";
+
+    let output = run_stdin(&scratch.0, &["-"], input);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "\n/* plain */\n\n\nx\n");
+}
+
+#[test]
+fn backup_never_clobbers_an_earlier_backup() {
+    let scratch = Scratch::new("backup");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    let first = run(&scratch.0, &["--write", "--backup", "a.txt"]);
+    assert_eq!(first.status.code(), Some(2), "{}", stderr(&first));
+    assert!(stdout(&first).contains("a.txt.bak"), "{}", stdout(&first));
+
+    scratch.write("a.txt", "na\u{ef}ve\n");
+    assert_eq!(
+        run(&scratch.0, &["--write", "--backup", "a.txt"])
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(scratch.read("a.txt"), "nave\n");
+    assert_eq!(scratch.read("a.txt.bak"), "caf\u{e9}\n");
+    assert_eq!(scratch.read("a.txt.bak.1"), "na\u{ef}ve\n");
+
+    let piped = run_stdin(&scratch.0, &["--backup", "-"], "x\n");
+    assert!(!piped.status.success());
+}
+
+#[test]
+fn json_report_is_a_single_object_with_stable_fields() {
+    let scratch = Scratch::new("json");
+    scratch.write("a.txt", "caf\u{e9}\n/// doc\n");
+
+    let output = run(&scratch.0, &["--json", "--dry-run", "a.txt"]);
+    let text = stdout(&output);
+    assert_eq!(text.lines().count(), 1, "{text}");
+    let report: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(report["path"], "a.txt");
+    assert_eq!(report["changed"], true);
+    assert_eq!(report["written"], false);
+    assert_eq!(report["skipped_lines"], 1);
+    assert_eq!(report["non_ascii_chars"], 1);
+    assert_eq!(report["non_ascii_bytes"], 2);
+    assert_eq!(report["positions"][0]["line"], 1);
+    assert_eq!(report["positions"][0]["column"], 4);
+    assert_eq!(report["positions"][0]["char"], "\u{e9}");
+    assert_eq!(report["watermarks"][0]["text"], "///");
+}
+
+#[test]
+fn several_files_are_cleaned_even_when_one_fails() {
+    let scratch = Scratch::new("batch");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.txt", "x\n");
+
+    let output = run(&scratch.0, &["--write", "a.txt", "missing.txt", "b.txt"]);
+    assert!(!output.status.success());
+    assert_eq!(scratch.read("a.txt"), "caf\n");
+    let report = stdout(&output);
+    assert!(report.contains("==> b.txt <=="), "{report}");
+    assert!(report.contains("Processed 2 files, 1 changed"), "{report}");
+    assert!(
+        stderr(&output).contains("missing.txt"),
+        "{}",
+        stderr(&output)
+    );
+
+    let mixed = run(&scratch.0, &["a.txt", "-"]);
+    assert!(!mixed.status.success());
+}
+
+#[test]
+fn directory_walk_filters_by_extension_and_depth() {
+    let scratch = Scratch::new("walk");
+    fs::create_dir_all(scratch.0.join("src/deep")).unwrap();
+    fs::create_dir_all(scratch.0.join(".hidden")).unwrap();
+    scratch.write("src/a.rs", "caf\u{e9}\n");
+    scratch.write("src/b.txt", "caf\u{e9}\n");
+    scratch.write("src/deep/c.rs", "caf\u{e9}\n");
+    scratch.write(".hidden/d.rs", "caf\u{e9}\n");
+
+    let output = run(
+        &scratch.0,
+        &["--write", "--ext", "rs", "--max-depth", "1", "src"],
+    );
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert_eq!(scratch.read("src/a.rs"), "caf\n");
+    assert_eq!(scratch.read("src/b.txt"), "caf\u{e9}\n");
+    assert_eq!(scratch.read("src/deep/c.rs"), "caf\u{e9}\n");
+
+    assert_eq!(run(&scratch.0, &["--write", "."]).status.code(), Some(2));
+    assert_eq!(scratch.read("src/deep/c.rs"), "caf\n");
+    assert_eq!(scratch.read(".hidden/d.rs"), "caf\u{e9}\n");
+}
+
+#[test]
+fn directory_walk_leaves_backups_alone() {
+    let scratch = Scratch::new("backups");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    assert_eq!(
+        run(&scratch.0, &["--write", "--backup", "."]).status.code(),
+        Some(2)
+    );
+    scratch.write("a.txt", "na\u{ef}ve\n");
+    assert_eq!(
+        run(&scratch.0, &["--write", "--backup", "."]).status.code(),
+        Some(2)
+    );
+
+    assert_eq!(scratch.read("a.txt.bak"), "caf\u{e9}\n");
+    assert_eq!(scratch.read("a.txt.bak.1"), "na\u{ef}ve\n");
+    // A .bak file with no source beside it is an ordinary file
+    scratch.write("orphan.bak", "\u{e9}\n");
+    let report = stdout(&run(&scratch.0, &["--dry-run", "."]));
+    assert!(report.contains("orphan.bak"), "{report}");
+    assert!(!report.contains("a.txt.bak"), "{report}");
+}
+
+#[test]
+fn invisible_characters_are_named_with_a_bidi_warning() {
+    let scratch = Scratch::new("invisible");
+    let output = run_stdin(&scratch.0, &["-"], "a\u{200b}b\u{202e}c\n");
+    assert_eq!(stdout(&output), "abc\n");
+    let report = stderr(&output);
+    assert!(
+        report.contains("line 1, col 2, byte 1: U+200B ZERO WIDTH SPACE"),
+        "{report}"
+    );
+    assert!(report.contains("U+202E RIGHT-TO-LEFT OVERRIDE"), "{report}");
+    assert!(report.contains("Trojan Source"), "{report}");
+    assert!(!report.contains("Non-ASCII positions"), "{report}");
+
+    let plain = stderr(&run_stdin(&scratch.0, &["-"], "a\u{200b}b\n"));
+    assert!(!plain.contains("Trojan Source"), "{plain}");
+}
+
+#[test]
+fn check_fails_on_dirty_files_without_writing() {
+    let scratch = Scratch::new("check");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.txt", "x\n");
+
+    let dirty = run(&scratch.0, &["--check", "a.txt", "b.txt"]);
+    assert_eq!(dirty.status.code(), Some(2));
+    let report = stdout(&dirty);
+    assert!(report.contains("a.txt: dirty (1 non-ASCII"), "{report}");
+    assert!(report.contains("b.txt: clean"), "{report}");
+    assert_eq!(scratch.read("a.txt"), "caf\u{e9}\n");
+
+    assert_eq!(
+        run(&scratch.0, &["--check", "b.txt"]).status.code(),
+        Some(0)
+    );
+}
+
+#[test]
+fn a_missing_final_newline_stays_missing() {
+    let scratch = Scratch::new("final_newline");
+    let output = run_stdin(&scratch.0, &["-"], "one\ncaf\u{e9}");
+    assert_eq!(stdout(&output), "one\ncaf");
+
+    scratch.write("a.txt", "plain\nlast");
+    let clean = run(&scratch.0, &["a.txt"]);
+    assert!(
+        stdout(&clean).contains("File is clean"),
+        "{}",
+        stdout(&clean)
+    );
+    assert_eq!(scratch.read("a.txt"), "plain\nlast");
+}
+
+#[test]
+fn allowed_characters_are_kept_and_not_reported() {
+    let scratch = Scratch::new("allow");
+    let output = run_stdin(
+        &scratch.0,
+        &["--allow", "\u{a9}\u{2192}", "-"],
+        "\u{a9} a\u{2192}b\u{e9}\n",
+    );
+    assert_eq!(stdout(&output), "\u{a9} a\u{2192}b\n");
+    let report = stderr(&output);
+    assert!(report.contains("Filtered 1 non-ASCII"), "{report}");
+    assert!(!report.contains("U+00A9"), "{report}");
+}
+
+#[test]
+fn entropy_threshold_separates_accents_from_payloads() {
+    let scratch = Scratch::new("entropy");
+    scratch.write("accent.txt", "caf\u{e9}\n");
+    let payload: String = (0..200)
+        .filter_map(|i| char::from_u32(0x4e00 + i * 37))
+        .collect();
+    scratch.write("payload.txt", &format!("{payload}\n"));
+
+    let accent = run(
+        &scratch.0,
+        &["--check", "--entropy-threshold", "3", "accent.txt"],
+    );
+    assert_eq!(accent.status.code(), Some(0), "{}", stdout(&accent));
+    let report = stdout(&run(
+        &scratch.0,
+        &["--dry-run", "--entropy-threshold", "3", "accent.txt"],
+    ));
+    assert!(!report.contains("Entropy"), "{report}");
+
+    let payload = run(
+        &scratch.0,
+        &["--check", "--entropy-threshold", "3", "payload.txt"],
+    );
+    assert_eq!(payload.status.code(), Some(2));
+    assert!(
+        stdout(&payload).contains("high entropy"),
+        "{}",
+        stdout(&payload)
+    );
+}
+
+#[test]
+fn diff_shows_the_changed_lines() {
+    let scratch = Scratch::new("diff");
+    scratch.write("a.txt", "a\ncaf\u{e9}\n/// x\n");
+
+    let output = run(&scratch.0, &["--diff", "--dry-run", "a.txt"]);
+    let report = stdout(&output);
+    assert!(
+        report.contains("--- a.txt\n+++ a.txt (filtered)\n"),
+        "{report}"
+    );
+    assert!(
+        report.contains("\n a\n-caf\u{e9}\n-/// x\n+caf\n+\n"),
+        "{report}"
+    );
+    assert_eq!(scratch.read("a.txt"), "a\ncaf\u{e9}\n/// x\n");
+}
+
+#[test]
+fn binary_files_are_skipped_unless_forced() {
+    let scratch = Scratch::new("binary");
+    scratch.write("bin.dat", "a\0caf\u{e9}\n");
+
+    let skipped = run(&scratch.0, &["bin.dat"]);
+    assert!(
+        stdout(&skipped).contains("NUL bytes"),
+        "{}",
+        stdout(&skipped)
+    );
+    assert_eq!(scratch.read("bin.dat"), "a\0caf\u{e9}\n");
+
+    assert_eq!(
+        run(&scratch.0, &["--write", "--force", "bin.dat"])
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(scratch.read("bin.dat"), "a\0caf\n");
+}
+
+#[test]
+fn whole_line_watermarks_leave_doc_comments_alone() {
+    let scratch = Scratch::new("whole_line");
+    let input = "  ///  \n/// Returns the length\nx\n";
+    let output = run_stdin(&scratch.0, &["--watermark-whole-line", "-"], input);
+    assert_eq!(stdout(&output), "\n/// Returns the length\nx\n");
+    assert_eq!(stdout(&run_stdin(&scratch.0, &["-"], input)), "\n\nx\n");
+}
+
+#[test]
+fn removed_watermark_lines_are_reported_with_their_text() {
+    let scratch = Scratch::new("skipped_lines");
+    let input = "ok\n  // Generated by a tool\n";
+    let report = stderr(&run_stdin(&scratch.0, &["-"], input));
+    assert!(
+        report.contains(
+            "line 2, col 3: // Generated by a tool (matched '// Generated by', generated-by)"
+        ),
+        "{report}"
+    );
+
+    let json = stderr(&run_stdin(&scratch.0, &["--json", "--dry-run", "-"], input));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        json["watermarks"][0]["line_text"],
+        "  // Generated by a tool"
+    );
+}
+
+#[test]
+fn parallel_jobs_clean_every_file_and_report_in_path_order() {
+    let scratch = Scratch::new("jobs");
+    let names = ["e.txt", "b.txt", "d.txt", "a.txt", "c.txt"];
+    for name in names {
+        scratch.write(name, "caf\u{e9}\n");
+    }
+
+    let mut args = vec!["--write", "--jobs", "4"];
+    args.extend(names);
+    let output = run(&scratch.0, &args);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    let report = stdout(&output);
+    let headers: Vec<&str> = report
+        .lines()
+        .filter(|line| line.starts_with("==>"))
+        .collect();
+    assert_eq!(
+        headers,
+        [
+            "==> a.txt <==",
+            "==> b.txt <==",
+            "==> c.txt <==",
+            "==> d.txt <==",
+            "==> e.txt <=="
+        ]
+    );
+    for name in names {
+        assert_eq!(scratch.read(name), "caf\n");
+    }
+}
+
+#[test]
+fn stats_only_prints_one_line_of_totals() {
+    let scratch = Scratch::new("stats_only");
+    let input = "caf\u{e9}\n/// x\n";
+    let report = stderr(&run_stdin(&scratch.0, &["--stats-only", "-"], input));
+    assert_eq!(report, "skipped=1 nonascii=1 invisible=0 entropy=1.00\n");
+
+    let json = stderr(&run_stdin(
+        &scratch.0,
+        &["--stats-only", "--json", "-"],
+        input,
+    ));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["skipped_lines"], 1);
+    assert!(json.get("positions").is_none(), "{json}");
+}
+
+#[test]
+fn characters_are_tallied_by_frequency() {
+    let scratch = Scratch::new("frequency");
+    let input = "\u{e9} \u{201c}a\u{201d} \u{201c}b\u{201d} \u{201c}\n";
+    let report = stderr(&run_stdin(&scratch.0, &["-"], input));
+    assert!(
+        report.contains("Characters: \u{201c} \u{d7}3, \u{201d} \u{d7}2, \u{e9} \u{d7}1\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn directory_walk_honours_gitignore_unless_told_not_to() {
+    let scratch = Scratch::new("gitignore");
+    let init = Command::new("git")
+        .arg("init")
+        .arg("-q")
+        .current_dir(&scratch.0)
+        .status();
+    assert!(init.unwrap().success());
+    fs::create_dir_all(scratch.0.join("target")).unwrap();
+    scratch.write(".gitignore", "target/\n");
+    scratch.write(".ignore", "local.txt\n");
+    scratch.write("target/out.txt", "caf\u{e9}\n");
+    scratch.write("local.txt", "caf\u{e9}\n");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    let report = stdout(&run(&scratch.0, &["--dry-run", "."]));
+    assert!(report.contains("a.txt"), "{report}");
+    assert!(!report.contains("out.txt"), "{report}");
+    assert!(!report.contains("local.txt"), "{report}");
+
+    let report = stdout(&run(&scratch.0, &["--dry-run", "--no-ignore", "."]));
+    assert!(report.contains("out.txt"), "{report}");
+    assert!(report.contains("local.txt"), "{report}");
+}
+
+#[test]
+fn colour_is_off_for_pipes_unless_forced() {
+    let scratch = Scratch::new("color");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    let piped = stdout(&run(&scratch.0, &["--dry-run", "a.txt"]));
+    assert!(!piped.contains('\x1b'), "{piped}");
+    let forced = stdout(&run(&scratch.0, &["--dry-run", "--color=always", "a.txt"]));
+    assert!(
+        forced.contains("\x1b[31m  line 1, col 4, byte 3:"),
+        "{forced}"
+    );
+    assert!(
+        !run(&scratch.0, &["--color=bogus", "a.txt"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn quiet_is_silent_for_clean_files_only() {
+    let scratch = Scratch::new("quiet");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.txt", "x\n");
+
+    let clean = run(&scratch.0, &["-q", "b.txt"]);
+    assert!(clean.status.success());
+    assert_eq!(stdout(&clean), "");
+
+    let report = stdout(&run(
+        &scratch.0,
+        &["--quiet", "--dry-run", "a.txt", "b.txt"],
+    ));
+    assert!(report.contains("==> a.txt <=="), "{report}");
+    assert!(!report.contains("b.txt"), "{report}");
+}
+
+#[test]
+fn detect_encoding_recognises_latin1_text() {
+    let scratch = Scratch::new("encoding");
+    fs::write(
+        scratch.0.join("latin1.txt"),
+        b"caf\xe9 cr\xe8me br\xfbl\xe9e\n",
+    )
+    .unwrap();
+
+    let detected = stdout(&run(
+        &scratch.0,
+        &["--dry-run", "--detect-encoding", "latin1.txt"],
+    ));
+    assert!(
+        detected.contains("Detected encoding: windows-1252"),
+        "{detected}"
+    );
+    assert!(
+        detected.contains("Characters: \u{e9} \u{d7}2, \u{e8} \u{d7}1, \u{fb} \u{d7}1"),
+        "{detected}"
+    );
+
+    let lossy = stdout(&run(&scratch.0, &["--dry-run", "latin1.txt"]));
+    assert!(lossy.contains("\u{fffd} \u{d7}4"), "{lossy}");
+}
+
+#[test]
+fn exit_codes_tell_why_the_tool_stopped() {
+    let scratch = Scratch::new("exit_codes");
+    scratch.write("clean.txt", "x\n");
+    scratch.write("dirty.txt", "caf\u{e9}\n");
+    scratch.write("bin.dat", "a\0b\n");
+    let code = |args: &[&str]| run(&scratch.0, args).status.code();
+
+    assert_eq!(code(&["clean.txt"]), Some(0));
+    assert_eq!(code(&["--no-such-flag", "clean.txt"]), Some(1));
+    assert_eq!(code(&["bin.dat", "clean.txt"]), Some(4));
+    assert_eq!(code(&["--dry-run", "bin.dat", "dirty.txt"]), Some(2));
+    assert_eq!(code(&["dirty.txt", "missing.txt"]), Some(3));
+}
+
+#[test]
+fn positions_carry_the_byte_offset_into_the_original() {
+    let scratch = Scratch::new("offsets");
+    let input = "a\n/// x\nb\u{e9}\n";
+    let report = stderr(&run_stdin(&scratch.0, &["--dry-run", "-"], input));
+    assert!(
+        report.contains("line 3, col 2, byte 9: '\u{e9}'"),
+        "{report}"
+    );
+
+    let json = stderr(&run_stdin(&scratch.0, &["--json", "--dry-run", "-"], input));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["positions"][0]["offset"], 9);
+}
+
+#[test]
+fn config_file_sets_defaults_that_flags_override() {
+    let scratch = Scratch::new("config");
+    scratch.write(
+        ".nonascii_filter.toml",
+        "replace = \"_\"\nallow = \"\\u00a9\"\n",
+    );
+    let input = "\u{a9} caf\u{e9}\n";
+
+    assert_eq!(
+        stdout(&run_stdin(&scratch.0, &["-"], input)),
+        "\u{a9} caf_\n"
+    );
+    let flagged = run_stdin(&scratch.0, &["--replace=#", "-"], input);
+    assert_eq!(stdout(&flagged), "\u{a9} caf#\n");
+
+    scratch.write("other.toml", "bogus = 1\n");
+    let bad = run_stdin(&scratch.0, &["--config", "other.toml", "-"], "x\n");
+    assert_eq!(bad.status.code(), Some(1));
+    assert!(
+        stderr(&bad).contains("unknown field `bogus`"),
+        "{}",
+        stderr(&bad)
+    );
+}
+
+#[test]
+fn kept_watermarks_are_audited_but_not_removed() {
+    let scratch = Scratch::new("keep_watermarks");
+    let input = "// SPDX-License-Identifier: MIT\nx\n";
+    let output = run_stdin(&scratch.0, &["--no-strip-watermarks", "-"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), input);
+    let report = stderr(&output);
+    assert!(
+        report.contains("Watermarks kept (1):\n  line 1, col 1:"),
+        "{report}"
+    );
+}
+
+#[test]
+fn no_progress_bar_when_stdout_is_not_a_terminal() {
+    let scratch = Scratch::new("progress");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.txt", "x\n");
+
+    let output = run(&scratch.0, &["--dry-run", "a.txt", "b.txt"]);
+    assert_eq!(stderr(&output), "");
+    assert!(
+        !stdout(&output).contains("2/2 files"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn files_are_only_modified_with_write() {
+    let scratch = Scratch::new("write");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    let report = run(&scratch.0, &["a.txt"]);
+    assert_eq!(report.status.code(), Some(2));
+    assert_eq!(scratch.read("a.txt"), "caf\u{e9}\n");
+    assert_eq!(
+        run(&scratch.0, &["--write", "--dry-run", "a.txt"])
+            .status
+            .code(),
+        Some(1)
+    );
+
+    for flag in ["--fix", "-w"] {
+        scratch.write("a.txt", "caf\u{e9}\n");
+        assert_eq!(run(&scratch.0, &[flag, "a.txt"]).status.code(), Some(2));
+        assert_eq!(scratch.read("a.txt"), "caf\n");
+    }
+}
+
+#[test]
+fn output_writes_a_clean_copy_and_leaves_the_source_alone() {
+    let scratch = Scratch::new("output");
+    fs::create_dir_all(scratch.0.join("src")).unwrap();
+    scratch.write("src/a.txt", "caf\u{e9}\n");
+    scratch.write("src/b.txt", "x\n");
+
+    let single = run(&scratch.0, &["-o", "copy.txt", "src/a.txt"]);
+    assert_eq!(single.status.code(), Some(2), "{}", stderr(&single));
+    assert_eq!(scratch.read("copy.txt"), "caf\n");
+    assert_eq!(scratch.read("src/a.txt"), "caf\u{e9}\n");
+
+    let not_a_dir = run(
+        &scratch.0,
+        &["--output", "copy.txt", "src/a.txt", "src/b.txt"],
+    );
+    assert_eq!(not_a_dir.status.code(), Some(1));
+
+    fs::create_dir_all(scratch.0.join("clean")).unwrap();
+    run(&scratch.0, &["--output", "clean", "src"]);
+    assert_eq!(scratch.read("clean/src/a.txt"), "caf\n");
+    assert_eq!(scratch.read("clean/src/b.txt"), "x\n");
+}
+
+#[test]
+fn hash_selects_the_digest_algorithm() {
+    let scratch = Scratch::new("hash");
+    let sha1 = stderr(&run_stdin(
+        &scratch.0,
+        &["--hash", "sha1", "-"],
+        "caf\u{e9}\n",
+    ));
+    assert!(
+        sha1.contains("Original SHA1: 6faf166142e6fa460e85841f3986681f91bd0ac2\n"),
+        "{sha1}"
+    );
+    assert!(
+        sha1.contains("Filtered SHA1: 478d5124e7d87b87bdcea2d2bcff345699a1f65e\n"),
+        "{sha1}"
+    );
+
+    let json = stderr(&run_stdin(
+        &scratch.0,
+        &["--hash", "blake3", "--json", "-"],
+        "x\n",
+    ));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["hash"], "blake3");
+    assert_eq!(json["original_digest"].as_str().map(str::len), Some(64));
+    assert!(json.get("original_sha256").is_none(), "{json}");
+
+    let unknown = run_stdin(&scratch.0, &["--hash", "md5", "-"], "x\n");
+    assert_eq!(unknown.status.code(), Some(1));
+}
+
+#[test]
+fn context_shows_the_lines_around_a_watermark() {
+    let scratch = Scratch::new("context");
+    let input = "a\nb\n/// x\nc\nd\n";
+    let report = stderr(&run_stdin(&scratch.0, &["--context", "1", "-"], input));
+    assert!(
+        report.contains("  2 | b\n> 3 | /// x\n  4 | c\n"),
+        "{report}"
+    );
+    assert!(!report.contains("1 | a"), "{report}");
+}
+
+#[cfg(unix)]
+#[test]
+fn writes_replace_the_file_keeping_its_mode_and_no_temp_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let scratch = Scratch::new("atomic");
+    let path = scratch.write("a.txt", "caf\u{e9}\n");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+    assert_eq!(
+        run(&scratch.0, &["--write", "a.txt"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(scratch.read("a.txt"), "caf\n");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    let names: Vec<_> = fs::read_dir(&scratch.0)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["a.txt"]);
+}
+
+#[test]
+fn sarif_log_lists_findings_with_percent_encoded_uris() {
+    let scratch = Scratch::new("sarif");
+    fs::create_dir_all(scratch.0.join("d/sp ace")).unwrap();
+    scratch.write("d/sp ace/\u{e9}.md", "caf\u{e9}\n/// doc\n");
+
+    let output = run(&scratch.0, &["--sarif", "d"]);
+    assert_eq!(output.status.code(), Some(2));
+    let log: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+    let results = &log["runs"][0]["results"];
+    assert_eq!(results[0]["ruleId"], "non-ascii");
+    assert_eq!(results[1]["ruleId"], "watermark");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "d/sp%20ace/%C3%A9.md");
+    assert_eq!(location["region"]["startLine"], 1);
+    assert_eq!(location["region"]["startColumn"], 4);
+}
+
+#[test]
+fn restore_puts_the_newest_backup_back() {
+    let scratch = Scratch::new("restore");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    run(&scratch.0, &["--write", "--backup", "a.txt"]);
+    scratch.write("a.txt", "na\u{ef}ve\n");
+    run(&scratch.0, &["--write", "--backup", "a.txt"]);
+
+    let restored = run(&scratch.0, &["restore", "a.txt"]);
+    assert_eq!(restored.status.code(), Some(0), "{}", stderr(&restored));
+    assert!(
+        stdout(&restored).contains("from a.txt.bak.1"),
+        "{}",
+        stdout(&restored)
+    );
+    assert_eq!(scratch.read("a.txt"), "na\u{ef}ve\n");
+    assert!(!scratch.0.join("a.txt.bak.1").exists());
+
+    run(&scratch.0, &["restore", "a.txt"]);
+    assert_eq!(scratch.read("a.txt"), "caf\u{e9}\n");
+    let none = run(&scratch.0, &["restore", "a.txt"]);
+    assert_eq!(none.status.code(), Some(3));
+    assert!(
+        stderr(&none).contains("no backup found"),
+        "{}",
+        stderr(&none)
+    );
+}
+
+#[test]
+fn check_passes_files_below_the_non_ascii_limit() {
+    let scratch = Scratch::new("max-pct");
+    scratch.write("a.txt", "a caf\u{e9} in plain text\n");
+
+    let check = |pct: &str| run(&scratch.0, &["--check", "--max-nonascii-pct", pct, "a.txt"]);
+    let below = check("50");
+    assert_eq!(below.status.code(), Some(0));
+    assert!(
+        stdout(&below).contains("% within limit"),
+        "{}",
+        stdout(&below)
+    );
+    assert_eq!(check("5").status.code(), Some(2));
+    assert_eq!(
+        run(&scratch.0, &["--check", "a.txt"]).status.code(),
+        Some(2)
+    );
+
+    scratch.write("b.txt", "/// doc\nx\n");
+    assert_eq!(
+        run(
+            &scratch.0,
+            &["--check", "--max-nonascii-pct", "50", "b.txt"]
+        )
+        .status
+        .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn fail_fast_stops_at_the_first_error() {
+    let scratch = Scratch::new("fail_fast");
+    scratch.write("b.txt", "caf\u{e9}\n");
+    scratch.write("c.txt", "caf\u{e9}\n");
+
+    let stopped = run(
+        &scratch.0,
+        &["--write", "--fail-fast", "a.txt", "b.txt", "c.txt"],
+    );
+    assert_eq!(stopped.status.code(), Some(3));
+    assert!(
+        stderr(&stopped).contains("2 files were not processed"),
+        "{}",
+        stderr(&stopped)
+    );
+    assert_eq!(scratch.read("b.txt"), "caf\u{e9}\n");
+
+    let kept_going = run(&scratch.0, &["--write", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(kept_going.status.code(), Some(3));
+    assert_eq!(scratch.read("b.txt"), "caf\n");
+    assert_eq!(scratch.read("c.txt"), "caf\n");
+}
+
+#[test]
+fn preserve_times_keeps_the_modification_time() {
+    use std::time::{Duration, SystemTime};
+
+    let scratch = Scratch::new("preserve_times");
+    let past = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    let modified = |name: &str| {
+        fs::metadata(scratch.0.join(name))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    for name in ["kept.txt", "touched.txt"] {
+        let path = scratch.write(name, "caf\u{e9}\n");
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+    }
+
+    run(&scratch.0, &["--write", "--preserve-times", "kept.txt"]);
+    assert_eq!(scratch.read("kept.txt"), "caf\n");
+    assert_eq!(modified("kept.txt"), past);
+
+    run(&scratch.0, &["--write", "touched.txt"]);
+    assert_ne!(modified("touched.txt"), past);
+}
+
+#[test]
+fn globs_are_expanded_by_the_tool() {
+    let scratch = Scratch::new("globs");
+    fs::create_dir_all(scratch.0.join("src/x")).unwrap();
+    scratch.write("src/x/a.rs", "caf\u{e9}\n");
+    scratch.write("src/b.txt", "caf\u{e9}\n");
+    scratch.write("lit*.txt", "caf\u{e9}\n");
+
+    let report = stdout(&run(&scratch.0, &["src/**/*.rs", "lit*.txt"]));
+    assert!(report.contains("==> src/x/a.rs <=="), "{report}");
+    assert!(report.contains("==> lit*.txt <=="), "{report}");
+    assert!(!report.contains("b.txt"), "{report}");
+
+    let none = run(&scratch.0, &["nope/*.rs"]);
+    assert_eq!(none.status.code(), Some(3));
+    assert!(
+        stderr(&none).contains("no files match"),
+        "{}",
+        stderr(&none)
+    );
+}
+
+#[test]
+fn summary_json_rolls_up_the_batch_on_stdout() {
+    let scratch = Scratch::new("summary_json");
+    scratch.write("a.txt", "caf\u{e9}\n/// doc\n");
+    scratch.write("b.txt", "x\n");
+    scratch.write("c.dat", "a\0b\n");
+
+    let output = run(&scratch.0, &["--summary-json", "a.txt", "b.txt", "c.dat"]);
+    assert!(
+        stderr(&output).contains("==> a.txt <=="),
+        "{}",
+        stderr(&output)
+    );
+    let summary: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(summary["files"], 3);
+    assert_eq!(summary["changed"], 1);
+    assert_eq!(summary["written"], 0);
+    assert_eq!(summary["binary_skipped"], 1);
+    assert_eq!(summary["skipped_lines"], 1);
+    assert_eq!(summary["non_ascii_bytes"], 2);
+    assert_eq!(summary["per_file"][0]["path"], "a.txt");
+    assert_eq!(summary["per_file"][2]["binary"], true);
+}
+
+#[cfg(unix)]
+#[test]
+fn links_are_left_alone_unless_followed_and_loops_are_skipped() {
+    use std::os::unix::fs::symlink;
+
+    let scratch = Scratch::new("symlinks");
+    fs::create_dir_all(scratch.0.join("tree/d")).unwrap();
+    fs::create_dir_all(scratch.0.join("outside")).unwrap();
+    scratch.write("tree/d/a.txt", "caf\u{e9}\n");
+    scratch.write("outside/b.txt", "caf\u{e9}\n");
+    symlink("../outside", scratch.0.join("tree/out")).unwrap();
+    symlink("..", scratch.0.join("tree/d/up")).unwrap();
+
+    let walked = run(&scratch.0, &["--write", "tree"]);
+    assert_eq!(walked.status.code(), Some(2), "{}", stderr(&walked));
+    assert_eq!(scratch.read("outside/b.txt"), "caf\u{e9}\n");
+
+    scratch.write("tree/d/a.txt", "caf\u{e9}\n");
+    let followed = run(&scratch.0, &["--write", "--follow-symlinks", "tree"]);
+    assert_eq!(followed.status.code(), Some(2), "{}", stderr(&followed));
+    assert_eq!(scratch.read("tree/d/a.txt"), "caf\n");
+    assert_eq!(scratch.read("outside/b.txt"), "caf\n");
+    assert!(!stderr(&followed).contains("loop"), "{}", stderr(&followed));
+    assert!(
+        fs::symlink_metadata(scratch.0.join("tree/out"))
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+
+    let logged = run(&scratch.0, &["-vv", "--check", "--follow-symlinks", "tree"]);
+    assert!(
+        stderr(&logged).contains("tree: not following File system loop"),
+        "{}",
+        stderr(&logged)
+    );
+}
+
+#[test]
+fn limit_truncates_only_the_listing() {
+    let scratch = Scratch::new("limit");
+    let report = stderr(&run_stdin(
+        &scratch.0,
+        &["--per-char", "--limit", "2", "-"],
+        "\u{e9}\u{e9}\u{e9}\u{e9}\n",
+    ));
+    assert!(
+        report.contains("Filtered 4 non-ASCII characters"),
+        "{report}"
+    );
+    assert!(
+        report.contains("byte 2: '\u{e9}' (U+00E9)\n  (... and 2 more)\n"),
+        "{report}"
+    );
+    assert!(!report.contains("byte 4:"), "{report}");
+}
+
+#[test]
+fn cache_skips_files_that_stayed_clean() {
+    let scratch = Scratch::new("cache");
+    scratch.write("a.txt", "x\n");
+
+    assert_eq!(
+        run(&scratch.0, &["--cache", "a.txt"]).status.code(),
+        Some(0)
+    );
+    assert!(scratch.0.join(".nonascii_filter.cache").is_file());
+    let cached = run(&scratch.0, &["--cache", "a.txt"]);
+    assert!(
+        stdout(&cached).contains("unchanged since the last run"),
+        "{}",
+        stdout(&cached)
+    );
+
+    scratch.write("a.txt", "caf\u{e9}\n");
+    let changed = run(&scratch.0, &["--cache", "a.txt"]);
+    assert_eq!(changed.status.code(), Some(2));
+    assert!(
+        stdout(&changed).contains("Filtered 1 non-ASCII"),
+        "{}",
+        stdout(&changed)
+    );
+}
+
+#[test]
+fn interactive_asks_before_each_write() {
+    let scratch = Scratch::new("interactive");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.txt", "caf\u{e9}\n");
+
+    let asked = run_stdin(&scratch.0, &["-i", "a.txt", "b.txt"], "y\nn\n");
+    assert_eq!(stdout(&asked).matches("Clean this file? [y/N]").count(), 2);
+    assert_eq!(scratch.read("a.txt"), "caf\n");
+    assert_eq!(scratch.read("b.txt"), "caf\u{e9}\n");
+
+    let yes = run_stdin(&scratch.0, &["--interactive", "--yes", "b.txt"], "");
+    assert!(!stdout(&yes).contains("[y/N]"), "{}", stdout(&yes));
+    assert_eq!(scratch.read("b.txt"), "caf\n");
+
+    assert_eq!(
+        run(&scratch.0, &["-i", "--json", "a.txt"]).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn files_from_reads_the_names_from_stdin() {
+    let scratch = Scratch::new("files_from");
+    scratch.write("a b.txt", "caf\u{e9}\n");
+    scratch.write("c.txt", "caf\u{e9}\n");
+
+    let lines = run_stdin(
+        &scratch.0,
+        &["--write", "--files-from", "-"],
+        "a b.txt\nc.txt\n",
+    );
+    assert_eq!(lines.status.code(), Some(2), "{}", stderr(&lines));
+    assert_eq!(scratch.read("a b.txt"), "caf\n");
+    assert_eq!(scratch.read("c.txt"), "caf\n");
+
+    scratch.write("a b.txt", "caf\u{e9}\n");
+    let nul = run_stdin(
+        &scratch.0,
+        &["--write", "-0", "--files-from", "-"],
+        "a b.txt\0missing.txt\0",
+    );
+    assert_eq!(nul.status.code(), Some(3));
+    assert_eq!(scratch.read("a b.txt"), "caf\n");
+}
+
+#[test]
+fn git_changed_cleans_only_files_that_differ_from_head() {
+    let scratch = Scratch::new("git_changed");
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(&scratch.0)
+            .status();
+        assert!(status.unwrap().success(), "git {args:?}");
+    };
+    git(&["init", "-q"]);
+    scratch.write("committed.txt", "caf\u{e9}\n");
+    scratch.write("gone.txt", "x\n");
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "base"]);
+
+    scratch.write("modified.txt", "caf\u{e9}\n");
+    scratch.write("staged.txt", "caf\u{e9}\n");
+    git(&["add", "staged.txt"]);
+    fs::remove_file(scratch.0.join("gone.txt")).unwrap();
+
+    let output = run(&scratch.0, &["--check", "--git-changed"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(report.contains("modified.txt: dirty"), "{report}");
+    assert!(report.contains("staged.txt: dirty"), "{report}");
+    assert!(!report.contains("committed.txt"), "{report}");
+    assert!(!report.contains("gone.txt"), "{report}");
+}
+
+#[test]
+fn exclude_exempts_files_matching_a_glob() {
+    let scratch = Scratch::new("exclude");
+    fs::create_dir_all(scratch.0.join("tree/docs")).unwrap();
+    scratch.write("tree/docs/a.md", "caf\u{e9}\n");
+    scratch.write("tree/b.txt", "caf\u{e9}\n");
+
+    let output = run(&scratch.0, &["--write", "--exclude", "tree/docs", "tree"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    let report = stdout(&output);
+    assert!(
+        report.contains("a.md: exempt by --exclude 'tree/docs'"),
+        "{report}"
+    );
+    assert!(
+        report.contains("Skipped 1 files exempt by --exclude"),
+        "{report}"
+    );
+    assert_eq!(scratch.read("tree/docs/a.md"), "caf\u{e9}\n");
+    assert_eq!(scratch.read("tree/b.txt"), "caf\n");
+
+    let clean = run(&scratch.0, &["--check", "--exclude", "*.md", "tree"]);
+    assert_eq!(clean.status.code(), Some(0), "{}", stdout(&clean));
+
+    let bad = run(&scratch.0, &["--exclude", "[", "tree"]);
+    assert_eq!(bad.status.code(), Some(1));
+    assert!(stderr(&bad).contains("invalid glob"));
+}
+
+#[test]
+fn report_file_records_reports_printed_to_stderr_and_stdout() {
+    let scratch = Scratch::new("report_file");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    run(&scratch.0, &["--report-file", "audit.txt", "a.txt"]);
+    run(
+        &scratch.0,
+        &["--json", "--report-file", "audit.txt", "a.txt"],
+    );
+    let recorded = scratch.read("audit.txt");
+    assert!(recorded.starts_with("==> a.txt <=="), "{recorded}");
+    assert!(recorded.contains("U+00E9"), "{recorded}");
+    assert!(recorded.contains("\"positions\""), "{recorded}");
+
+    let piped = run_stdin(
+        &scratch.0,
+        &["--report-file", "stdin.txt", "-"],
+        "caf\u{e9}\n",
+    );
+    assert_eq!(piped.stdout, b"caf\n");
+    let recorded = scratch.read("stdin.txt");
+    assert!(recorded.contains("==> <stdin> <=="), "{recorded}");
+    assert!(stderr(&piped).contains("U+00E9"));
+
+    let sarif = run(
+        &scratch.0,
+        &["--sarif", "--report-file", "sarif.json", "a.txt"],
+    );
+    assert!(scratch.read("sarif.json").contains("sarif-2.1.0"));
+    assert!(stdout(&sarif).contains("sarif-2.1.0"));
+
+    run(
+        &scratch.0,
+        &["--summary-json", "--report-file", "summary.json", "a.txt"],
+    );
+    assert!(scratch.read("summary.json").contains("\"per_file\""));
+}
+
+#[test]
+fn print0_emits_one_nul_terminated_record_per_character() {
+    let scratch = Scratch::new("print0");
+    scratch.write("a b.txt", "caf\u{e9}\n\u{200b}x\n");
+
+    let output = run(&scratch.0, &["--print0", "a b.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        stdout(&output),
+        "a b.txt\t1\t4\t\u{e9}\0a b.txt\t2\t1\t\u{200b}\0"
+    );
+}
+
+#[test]
+fn verify_reads_written_files_back() {
+    let scratch = Scratch::new("verify");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    let output = run(&scratch.0, &["--write", "--verify", "a.txt"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Verified: read back clean and matching its digest."));
+    assert_eq!(scratch.read("a.txt"), "caf\n");
+
+    let alone = run(&scratch.0, &["--verify", "a.txt"]);
+    assert_eq!(alone.status.code(), Some(1));
+    assert!(stderr(&alone).contains("add --write"));
+}
+
+#[test]
+fn gzip_files_are_cleaned_inside_their_compression() {
+    use flate2::Compression;
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use std::io::Read;
+
+    let scratch = Scratch::new("gzip");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all("caf\u{e9}\n".as_bytes()).unwrap();
+    let path = scratch.0.join("log.gz");
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let raw = run(&scratch.0, &["--check", "--no-decompress", "log.gz"]);
+    assert_eq!(raw.status.code(), Some(4), "{}", stdout(&raw));
+
+    let output = run(&scratch.0, &["--write", "log.gz"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(stdout(&output).contains("U+00E9"));
+    let mut text = String::new();
+    GzDecoder::new(fs::File::open(&path).unwrap())
+        .read_to_string(&mut text)
+        .unwrap();
+    assert_eq!(text, "caf\n");
+}
+
+#[test]
+fn empty_action_decides_about_files_cleaned_to_whitespace() {
+    let scratch = Scratch::new("empty_action");
+    let only = "\u{2014}\u{2014}\n";
+    let clean = |action: Option<&str>| {
+        scratch.write("a.txt", only);
+        let mut args = vec!["--write"];
+        if let Some(action) = action {
+            args.extend(["--empty-action", action]);
+        }
+        args.push("a.txt");
+        let status = run(&scratch.0, &args).status.code();
+        (status, scratch.read("a.txt"))
+    };
+
+    assert_eq!(clean(None), (Some(0), only.to_string()));
+    assert_eq!(clean(Some("skip")), (Some(0), only.to_string()));
+    assert_eq!(clean(Some("truncate")), (Some(2), String::new()));
+    assert_eq!(clean(Some("newline")), (Some(2), "\n".to_string()));
+    assert_eq!(clean(Some("error")), (Some(3), only.to_string()));
+
+    scratch.write("a.txt", only);
+    let check = run(&scratch.0, &["--check", "a.txt"]);
+    assert!(
+        !stdout(&check).contains("--empty-action skip"),
+        "{}",
+        stdout(&check)
+    );
+}
+
+#[test]
+fn stdin_name_labels_piped_input_in_reports() {
+    let scratch = Scratch::new("stdin_name");
+
+    let plain = run_stdin(&scratch.0, &["--check", "-"], "caf\u{e9}\n");
+    assert!(
+        stderr(&plain).starts_with("<stdin>: dirty"),
+        "{}",
+        stderr(&plain)
+    );
+
+    let named = run_stdin(
+        &scratch.0,
+        &["--json", "--stdin-name", "src/a.rs", "-"],
+        "caf\u{e9}\n",
+    );
+    let report: serde_json::Value = serde_json::from_str(&stderr(&named)).unwrap();
+    assert_eq!(report["path"], "src/a.rs");
+}
+
+#[test]
+fn extract_keeps_what_would_be_removed() {
+    let scratch = Scratch::new("extract");
+    let text = "caf\u{e9}\n// Generated by x\n";
+    scratch.write("a.txt", text);
+
+    let piped = run(
+        &scratch.0,
+        &["--extract", "-", "--extract-watermarks", "a.txt"],
+    );
+    assert_eq!(stdout(&piped), "\u{e9}// Generated by x\n");
+    assert!(stderr(&piped).contains("U+00E9"), "{}", stderr(&piped));
+
+    let output = run(&scratch.0, &["--extract", "removed.bin", "a.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(scratch.read("removed.bin"), "\u{e9}");
+    assert_eq!(scratch.read("a.txt"), text);
+
+    let bad = run(&scratch.0, &["--extract", "x", "--write", "a.txt"]);
+    assert_eq!(bad.status.code(), Some(1));
+}
+
+#[test]
+fn verbosity_logs_to_stderr_only() {
+    let scratch = Scratch::new("verbose");
+    scratch.write("a.txt", "caf\u{e9}\n");
+
+    let silent = run(&scratch.0, &["--check", "a.txt"]);
+    assert_eq!(stderr(&silent), "");
+
+    let info = run(&scratch.0, &["-v", "--check", "a.txt"]);
+    assert!(stderr(&info).contains("INFO"), "{}", stderr(&info));
+    assert!(!stderr(&info).contains("DEBUG"), "{}", stderr(&info));
+    assert!(stdout(&info).ends_with(&stdout(&silent)));
+
+    let debug = run(&scratch.0, &["-vv", "--check", "a.txt"]);
+    assert!(
+        stderr(&debug).contains("a.txt: read 6 bytes"),
+        "{}",
+        stderr(&debug)
+    );
+}
+
+#[test]
+fn post_passes_tidy_the_cleaned_content() {
+    let scratch = Scratch::new("post");
+    scratch.write("a.txt", "caf\u{e9} \u{2014}\t\nend");
+
+    let output = run(
+        &scratch.0,
+        &[
+            "--write",
+            "--post",
+            "trim-trailing-ws,ensure-final-newline",
+            "a.txt",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert_eq!(scratch.read("a.txt"), "caf\nend\n");
+
+    let bad = run(&scratch.0, &["--post", "tidy", "a.txt"]);
+    assert_eq!(bad.status.code(), Some(1));
+}
+
+#[test]
+fn cache_is_dropped_when_post_passes_change() {
+    let scratch = Scratch::new("cache_post");
+    scratch.write("a.txt", "trailing  \n");
+
+    let plain = ["--cache", "--check", "a.txt"];
+    assert_eq!(run(&scratch.0, &plain).status.code(), Some(0));
+    let post = ["--cache", "--check", "--post", "trim-trailing-ws", "a.txt"];
+    assert_eq!(run(&scratch.0, &post).status.code(), Some(2));
+}
+
+#[test]
+fn validate_follows_the_config_and_exits_by_findings() {
+    let scratch = Scratch::new("validate");
+    scratch.write("a.txt", "caf\u{e9}\n");
+    scratch.write("b.md", "plain\n");
+
+    let found = run(&scratch.0, &["validate", "."]);
+    assert_eq!(found.status.code(), Some(2));
+    let listed = stdout(&found);
+    assert!(
+        listed.contains("a.txt:1:4: non-ASCII '\u{e9}' (U+00E9)"),
+        "{listed}"
+    );
+
+    let md = run(&scratch.0, &["validate", "--ext", "md", "."]);
+    assert_eq!(md.status.code(), Some(0));
+    let excluded = run(&scratch.0, &["validate", "--exclude", "*.txt", "."]);
+    assert_eq!(excluded.status.code(), Some(0));
+    scratch.write(".nonascii_filter.toml", "allow = \"\u{e9}\"\n");
+    assert_eq!(run(&scratch.0, &["validate", "."]).status.code(), Some(0));
+
+    let missing = run(&scratch.0, &["validate", "missing.txt"]);
+    assert_eq!(missing.status.code(), Some(3));
+    let write = run(&scratch.0, &["validate", "--write", "."]);
+    assert_eq!(write.status.code(), Some(1));
+}
+
+#[test]
+fn validate_fails_on_binary_files_unless_forced() {
+    let scratch = Scratch::new("validate_binary");
+    fs::write(scratch.0.join("plain.bin"), b"ascii\0only\n").unwrap();
+
+    let binary = run(&scratch.0, &["validate", "plain.bin"]);
+    assert_eq!(binary.status.code(), Some(2));
+    assert!(stdout(&binary).contains("add --force to validate it"));
+
+    let forced = run(&scratch.0, &["validate", "--force", "plain.bin"]);
+    assert_eq!(forced.status.code(), Some(0), "{}", stdout(&forced));
+
+    fs::write(scratch.0.join("dirty.bin"), b"caf\xc3\xa9\0\n").unwrap();
+    let dirty = run(&scratch.0, &["validate", "--force", "dirty.bin"]);
+    assert_eq!(dirty.status.code(), Some(2));
+    assert!(
+        stdout(&dirty).contains("dirty.bin:1:4:"),
+        "{}",
+        stdout(&dirty)
+    );
+}
+
+#[test]
+fn tab_width_expands_only_text_report_columns() {
+    let scratch = Scratch::new("tab_width");
+    scratch.write("a.txt", "\t\u{e9}\n");
+
+    let text = run(&scratch.0, &["--tab-width", "4", "a.txt"]);
+    assert!(
+        stdout(&text).contains("line 1, col 5, byte 1:"),
+        "{}",
+        stdout(&text)
+    );
+
+    let json = run(&scratch.0, &["--json", "--tab-width", "4", "a.txt"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&json)).unwrap();
+    assert_eq!(report["positions"][0]["column"], 2);
+
+    let sarif = run(&scratch.0, &["--sarif", "--tab-width", "4", "a.txt"]);
+    let log: serde_json::Value = serde_json::from_str(&stdout(&sarif)).unwrap();
+    let region = &log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startColumn"], 2);
+
+    let zero = run(&scratch.0, &["--tab-width", "0", "a.txt"]);
+    assert_eq!(zero.status.code(), Some(1));
+}
+
+#[test]
+fn threads_per_file_reports_what_a_serial_scan_does() {
+    let scratch = Scratch::new("threads_per_file");
+    let text: String = (0..200)
+        .map(|i| format!("line {i} caf\u{e9} \u{200b}\n"))
+        .collect();
+    scratch.write("a.txt", &text);
+
+    let serial = run(&scratch.0, &["--json", "a.txt"]);
+    let chunked = run(&scratch.0, &["--json", "--threads-per-file", "4", "a.txt"]);
+    assert_eq!(chunked.status.code(), Some(2), "{}", stderr(&chunked));
+    assert_eq!(stdout(&chunked), stdout(&serial));
+}
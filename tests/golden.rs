@@ -0,0 +1,122 @@
+/*
+   MIT License
+
+   Copyright (c) 2025 [Ehud (Udi) Shamir]
+
+   Permission is hereby granted, free of charge, to any person obtaining a copy
+   of this software and associated documentation files (the "Software"), to deal
+   in the Software without restriction, including without limitation the rights to
+   use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+   the Software, and to permit persons to whom the Software is furnished to do so,
+   subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in all
+   copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+   INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR
+   PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE
+   FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR
+   OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR
+   OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+// Golden-file tests: every directory below tests/fixtures holds an input.txt
+// that is piped through the binary with --json, an optional args file with
+// one extra flag per line, and the expected.txt and expected.json it must
+// produce. Run with UPDATE_GOLDEN=1 to rewrite the expected files after an
+// intended change.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use similar::TextDiff;
+
+fn fixtures() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut dirs: Vec<PathBuf> = fs::read_dir(&root)
+        .expect("tests/fixtures is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+// Returns the cleaned output and the pretty-printed JSON report
+fn run(fixture: &Path) -> (Vec<u8>, String) {
+    let input = fs::read(fixture.join("input.txt")).unwrap();
+    let extra = fs::read_to_string(fixture.join("args")).unwrap_or_default();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_remove_water"))
+        .arg("--json")
+        .args(extra.lines().filter(|line| !line.trim().is_empty()))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        matches!(output.status.code(), Some(0) | Some(2)),
+        "{} exited with {}: {stderr}",
+        fixture.display(),
+        output.status
+    );
+    let report: serde_json::Value = serde_json::from_str(&stderr)
+        .unwrap_or_else(|err| panic!("{}: bad JSON report: {err}\n{stderr}", fixture.display()));
+    let report = serde_json::to_string_pretty(&report).unwrap() + "\n";
+    (output.stdout, report)
+}
+
+// Compares one golden file, returning a unified diff when it differs
+fn compare(path: &Path, actual: &[u8], update: bool) -> Option<String> {
+    let expected = fs::read(path).unwrap_or_default();
+    if expected == actual {
+        return None;
+    }
+    if update {
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+    let expected = String::from_utf8_lossy(&expected);
+    let actual = String::from_utf8_lossy(actual);
+    let name = path.display().to_string();
+    let diff = TextDiff::from_lines(expected.as_ref(), actual.as_ref())
+        .unified_diff()
+        .header(&name, "actual")
+        .to_string();
+    // Line ending and trailing whitespace changes are invisible in a plain diff
+    Some(diff.replace('\r', "\\r"))
+}
+
+#[test]
+fn fixtures_match_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    let dirs = fixtures();
+    assert!(!dirs.is_empty(), "no fixtures found");
+    for fixture in &dirs {
+        let (cleaned, report) = run(fixture);
+        failures.extend(compare(&fixture.join("expected.txt"), &cleaned, update));
+        failures.extend(compare(
+            &fixture.join("expected.json"),
+            report.as_bytes(),
+            update,
+        ));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} golden file(s) differ, rerun with UPDATE_GOLDEN=1 if the change is intended:\n\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}